@@ -0,0 +1,108 @@
+use vector::*;
+use ray::*;
+use std::f64::*;
+
+// Axis aligned bounding box. The empty box has min above max, so growing it by anything gives that thing
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector
+}
+
+impl Aabb {
+    pub fn new(min: Vector, max: Vector) -> Aabb {
+        Aabb {
+            min: min,
+            max: max
+        }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb::new(Vector::one() * INFINITY, Vector::one() * NEG_INFINITY)
+    }
+
+    pub fn infinite() -> Aabb {
+        Aabb::new(Vector::one() * NEG_INFINITY, Vector::one() * INFINITY)
+    }
+
+    pub fn around(center: Vector, half_extents: Vector) -> Aabb {
+        Aabb::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn union(self, other: Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn intersection(self, other: Aabb) -> Aabb {
+        Aabb::new(self.min.max(other.min), self.max.min(other.max))
+    }
+
+    pub fn grow(self, point: Vector) -> Aabb {
+        Aabb::new(self.min.min(point), self.max.max(point))
+    }
+
+    // Pushes every face out by the amount
+    pub fn expand(self, amount: f64) -> Aabb {
+        Aabb::new(self.min - Vector::one() * amount, self.max + Vector::one() * amount)
+    }
+
+    pub fn corners(&self) -> [Vector; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vector::new(a.x, a.y, a.z), Vector::new(b.x, a.y, a.z), Vector::new(a.x, b.y, a.z), Vector::new(b.x, b.y, a.z),
+            Vector::new(a.x, a.y, b.z), Vector::new(b.x, a.y, b.z), Vector::new(a.x, b.y, b.z), Vector::new(b.x, b.y, b.z)
+        ]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min.x.is_finite() && self.min.y.is_finite() && self.min.z.is_finite() &&
+            self.max.x.is_finite() && self.max.y.is_finite() && self.max.z.is_finite()
+    }
+
+    pub fn centroid(&self) -> Vector {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    // Slab test, giving the distances along the ray where it enters and leaves the box, clipped to
+    // the part of the ray ahead of the origin. Takes the inverse of the ray direction so traversals
+    // only compute it once
+    pub fn ray_span(&self, ray: &Ray, inverse_direction: Vector) -> Option<(f64, f64)> {
+        if self.is_empty() {
+            return None;
+        }
+        let t1 = (self.min - ray.origin) * inverse_direction;
+        let t2 = (self.max - ray.origin) * inverse_direction;
+        // A ray parallel to a slab and starting on its edge gives NaN, which min and max skip over
+        let near = t1.min(t2);
+        let far = t1.max(t2);
+        let enter = near.x.max(near.y).max(near.z).max(0.0);
+        let exit = far.x.min(far.y).min(far.z);
+        if enter <= exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+
+    // Same as ray_span, for a single test
+    pub fn clip(&self, ray: &Ray) -> Option<(f64, f64)> {
+        self.ray_span(ray, Vector::one() / ray.direction)
+    }
+
+    // Distance from the point to the box, zero inside
+    pub fn distance(&self, pos: Vector) -> f64 {
+        (self.min - pos).max(pos - self.max).max(Vector::zero()).length()
+    }
+}
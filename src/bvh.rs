@@ -0,0 +1,360 @@
+use vector::*;
+use aabb::*;
+use ray::*;
+use std::f64::*;
+
+// Items stop being split once a node holds this few of them
+const MAX_LEAF_SIZE: usize = 4;
+// Centroids are sorted into this many bins per axis when looking for the cheapest split
+const SAH_BINS: usize = 12;
+// Cost of visiting a node relative to testing one item
+const TRAVERSAL_COST: f64 = 1.0;
+
+#[derive(Copy, Clone, Debug)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    // Leaves hold count items starting at first in the item order, other nodes have their two
+    // children at first and first + 1
+    pub first: usize,
+    pub count: usize
+}
+
+// Bounding volume hierarchy over a list of boxes, split by the surface area heuristic. It only
+// keeps the item indices, the owner keeps the items and tests them in the traversal callbacks
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub items: Vec<usize>
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            items: (0..bounds.len()).collect()
+        };
+        if !bounds.is_empty() {
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::empty(),
+                first: 0,
+                count: bounds.len()
+            });
+            bvh.build(0, bounds);
+        }
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.bounds)
+    }
+
+    fn build(&mut self, index: usize, bounds: &[Aabb]) {
+        let (first, count) = (self.nodes[index].first, self.nodes[index].count);
+        let mut node_bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for &item in &self.items[first..first + count] {
+            node_bounds = node_bounds.union(bounds[item]);
+            centroid_bounds = centroid_bounds.grow(bounds[item].centroid());
+        }
+        self.nodes[index].bounds = node_bounds;
+        if count <= MAX_LEAF_SIZE {
+            return;
+        }
+
+        let split = match self.find_split(first, count, bounds, node_bounds, centroid_bounds) {
+            Some(split) => split,
+            None => return
+        };
+
+        // The left child is every item on the low side of the split, moved to the front
+        let mut middle = first;
+        for i in first..first + count {
+            if split.side(bounds[self.items[i]].centroid()) {
+                self.items.swap(i, middle);
+                middle = middle + 1;
+            }
+        }
+        if middle == first || middle == first + count {
+            return;
+        }
+
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode { bounds: Aabb::empty(), first: first, count: middle - first });
+        self.nodes.push(BvhNode { bounds: Aabb::empty(), first: middle, count: first + count - middle });
+        self.nodes[index].first = left;
+        self.nodes[index].count = 0;
+        self.build(left, bounds);
+        self.build(left + 1, bounds);
+    }
+
+    // Cheapest binned split of the node, or none when keeping it a leaf is cheaper. Large nodes are
+    // always split, in half along their longest axis if the bins can't separate them
+    fn find_split(&self, first: usize, count: usize, bounds: &[Aabb], node_bounds: Aabb, centroid_bounds: Aabb) -> Option<Split> {
+        let mut best: Option<(Split, f64)> = None;
+        for axis in 0..3 {
+            let low = centroid_bounds.min.component(axis);
+            let high = centroid_bounds.max.component(axis);
+            if !(high > low) {
+                continue;
+            }
+
+            let bin_of = |centroid: Vector| {
+                let bin = ((centroid.component(axis) - low) / (high - low) * SAH_BINS as f64) as usize;
+                bin.min(SAH_BINS - 1)
+            };
+            let mut bin_bounds = [Aabb::empty(); SAH_BINS];
+            let mut bin_counts = [0; SAH_BINS];
+            for &item in &self.items[first..first + count] {
+                let bin = bin_of(bounds[item].centroid());
+                bin_bounds[bin] = bin_bounds[bin].union(bounds[item]);
+                bin_counts[bin] = bin_counts[bin] + 1;
+            }
+
+            // Sweep from the right to know the cost of everything above each boundary
+            let mut right_area = [0.0; SAH_BINS];
+            let mut right_count = [0; SAH_BINS];
+            let mut accumulated = Aabb::empty();
+            let mut accumulated_count = 0;
+            for bin in (1..SAH_BINS).rev() {
+                accumulated = accumulated.union(bin_bounds[bin]);
+                accumulated_count = accumulated_count + bin_counts[bin];
+                right_area[bin] = accumulated.surface_area();
+                right_count[bin] = accumulated_count;
+            }
+
+            let mut left_bounds = Aabb::empty();
+            let mut left_count = 0;
+            for boundary in 1..SAH_BINS {
+                left_bounds = left_bounds.union(bin_bounds[boundary - 1]);
+                left_count = left_count + bin_counts[boundary - 1];
+                if left_count == 0 || right_count[boundary] == 0 {
+                    continue;
+                }
+                let cost = left_bounds.surface_area() * left_count as f64 + right_area[boundary] * right_count[boundary] as f64;
+                if best.map_or(true, |(_, best_cost)| cost < best_cost) {
+                    let position = low + (high - low) * boundary as f64 / SAH_BINS as f64;
+                    best = Some((Split { axis: axis, position: position }, cost));
+                }
+            }
+        }
+
+        let leaf_cost = count as f64;
+        match best {
+            Some((split, cost)) => {
+                let split_cost = TRAVERSAL_COST + cost / node_bounds.surface_area().max(MIN_POSITIVE);
+                if split_cost < leaf_cost || count > 4 * MAX_LEAF_SIZE {
+                    Some(split)
+                } else {
+                    None
+                }
+            },
+            None if count > 4 * MAX_LEAF_SIZE => {
+                let size = centroid_bounds.max - centroid_bounds.min;
+                let axis = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+                let position = centroid_bounds.centroid().component(axis);
+                if size.component(axis) > 0.0 {
+                    Some(Split { axis: axis, position: position })
+                } else {
+                    None
+                }
+            },
+            None => None
+        }
+    }
+
+    // Calls hit for the items whose boxes the ray passes through, nearer boxes first, skipping boxes
+    // beyond the closest hit so far. Hit gets the item and the current closest distance, and returns
+    // the distance to the item if the ray hits it closer than that
+    pub fn ray_cast<F: FnMut(usize, f64) -> Option<f64>>(&self, ray: &Ray, max_distance: f64, mut hit: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inverse_direction = Vector::one() / ray.direction;
+        let mut closest = max_distance;
+        let mut stack = Vec::with_capacity(64);
+        if let Some((enter, _)) = self.nodes[0].bounds.ray_span(ray, inverse_direction) {
+            stack.push((0, enter));
+        }
+
+        while let Some((index, enter)) = stack.pop() {
+            if enter > closest {
+                continue;
+            }
+            let node = self.nodes[index];
+            if node.count > 0 {
+                for &item in &self.items[node.first..node.first + node.count] {
+                    if let Some(t) = hit(item, closest) {
+                        closest = closest.min(t);
+                    }
+                }
+                continue;
+            }
+
+            let left = self.nodes[node.first].bounds.ray_span(ray, inverse_direction);
+            let right = self.nodes[node.first + 1].bounds.ray_span(ray, inverse_direction);
+            match (left, right) {
+                (Some((left_enter, _)), Some((right_enter, _))) => {
+                    // Pushed far first so the near child is visited first
+                    if left_enter <= right_enter {
+                        stack.push((node.first + 1, right_enter));
+                        stack.push((node.first, left_enter));
+                    } else {
+                        stack.push((node.first, left_enter));
+                        stack.push((node.first + 1, right_enter));
+                    }
+                },
+                (Some((left_enter, _)), None) => stack.push((node.first, left_enter)),
+                (None, Some((right_enter, _))) => stack.push((node.first + 1, right_enter)),
+                (None, None) => ()
+            }
+        }
+    }
+
+    // Calls visit for every item whose box the ray passes through, in no particular order
+    pub fn along_ray<F: FnMut(usize)>(&self, ray: &Ray, mut visit: F) {
+        self.ray_cast(ray, INFINITY, |item, _| {
+            visit(item);
+            None
+        });
+    }
+
+    // Calls distance for the items that could be closest to the point, nearer boxes first. Distance
+    // returns the item's distance, and boxes farther than the closest one so far are skipped. Items
+    // are assumed to lie inside their boxes, so their distance is at least the box distance
+    pub fn closest<F: FnMut(usize) -> f64>(&self, pos: Vector, mut distance: F) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let mut closest = INFINITY;
+        let mut stack = Vec::with_capacity(64);
+        stack.push((0, self.nodes[0].bounds.distance(pos)));
+
+        while let Some((index, box_distance)) = stack.pop() {
+            // Boxes around the point can hold items with negative distances, so are never skipped
+            if box_distance > 0.0 && box_distance >= closest {
+                continue;
+            }
+            let node = self.nodes[index];
+            if node.count > 0 {
+                for &item in &self.items[node.first..node.first + node.count] {
+                    closest = closest.min(distance(item));
+                }
+                continue;
+            }
+
+            let left = self.nodes[node.first].bounds.distance(pos);
+            let right = self.nodes[node.first + 1].bounds.distance(pos);
+            if left <= right {
+                stack.push((node.first + 1, right));
+                stack.push((node.first, left));
+            } else {
+                stack.push((node.first, left));
+                stack.push((node.first + 1, right));
+            }
+        }
+    }
+}
+
+// Items with their centroid below the position along the axis go to the left child
+#[derive(Copy, Clone, Debug)]
+struct Split {
+    axis: usize,
+    position: f64
+}
+
+impl Split {
+    fn side(&self, centroid: Vector) -> bool {
+        centroid.component(self.axis) < self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geometry::*;
+
+    // Deterministic scatter of spheres, some overlapping and some far apart
+    fn spheres() -> Vec<(Vector, f64)> {
+        let mut state: u64 = 12345;
+        let mut next = || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        };
+        (0..300).map(|_| (Vector::new(next(), next(), next()) * 20.0 - Vector::one() * 10.0, 0.1 + next())).collect()
+    }
+
+    fn build(spheres: &[(Vector, f64)]) -> Bvh {
+        let bounds: Vec<Aabb> = spheres.iter().map(|&(center, radius)| Aabb::around(center, Vector::one() * radius)).collect();
+        Bvh::new(&bounds)
+    }
+
+    fn sphere_hit(sphere: (Vector, f64), ray: &Ray) -> Option<f64> {
+        match sphere_span(sphere.0, sphere.1, ray.origin, ray.direction) {
+            Some((start, _)) if start > 0.0 => Some(start),
+            _ => None
+        }
+    }
+
+    fn rays() -> Vec<Ray> {
+        (0..500).map(|i| {
+            let angle = i as f64 * 0.37;
+            let origin = Vector::new(angle.cos(), (angle * 0.7).sin(), angle.sin()) * 15.0;
+            let target = Vector::new((angle * 1.3).sin(), (angle * 2.1).cos(), (angle * 0.3).sin()) * 8.0;
+            Ray::new(origin, (target - origin).normalize())
+        }).collect()
+    }
+
+    #[test]
+    fn ray_casts_find_the_closest_hit() {
+        let spheres = spheres();
+        let bvh = build(&spheres);
+        for ray in rays() {
+            let expected = spheres.iter().filter_map(|&sphere| sphere_hit(sphere, &ray)).fold(INFINITY, f64::min);
+            let mut closest = INFINITY;
+            bvh.ray_cast(&ray, INFINITY, |item, closest_t| {
+                match sphere_hit(spheres[item], &ray) {
+                    Some(t) if t < closest_t => {
+                        closest = t;
+                        Some(t)
+                    },
+                    _ => None
+                }
+            });
+            assert_eq!(closest, expected);
+        }
+    }
+
+    #[test]
+    fn along_ray_visits_every_hit_item() {
+        let spheres = spheres();
+        let bvh = build(&spheres);
+        for ray in rays() {
+            let mut visited = vec![false; spheres.len()];
+            bvh.along_ray(&ray, |item| visited[item] = true);
+            for (index, &sphere) in spheres.iter().enumerate() {
+                if sphere_span(sphere.0, sphere.1, ray.origin, ray.direction).map_or(false, |(_, end)| end > 0.0) {
+                    assert!(visited[index], "sphere {} was skipped", index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn closest_finds_the_nearest_item() {
+        let spheres = spheres();
+        let bvh = build(&spheres);
+        let distance = |sphere: (Vector, f64), pos: Vector| (pos - sphere.0).length() - sphere.1;
+        for ray in rays() {
+            for &t in [0.0, 5.0, 15.0, 30.0].iter() {
+                let pos = ray.at(t);
+                let expected = spheres.iter().map(|&sphere| distance(sphere, pos)).fold(INFINITY, f64::min);
+                let mut closest = INFINITY;
+                bvh.closest(pos, |item| {
+                    let dist = distance(spheres[item], pos);
+                    closest = closest.min(dist);
+                    dist
+                });
+                assert_eq!(closest, expected);
+            }
+        }
+    }
+}
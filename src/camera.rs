@@ -0,0 +1,121 @@
+use std::f64::*;
+
+use vector::*;
+
+pub const UP: Vector = Vector {
+    x: 0.0,
+    y: 1.0,
+    z: 0.0
+};
+
+#[derive(Copy, Clone)]
+pub struct Camera {
+    pub position: Vector,
+    pub target: Vector,
+    pub up: Vector,
+    pub fov: f64,
+    // None uses the aspect ratio of the image being rendered
+    pub aspect_ratio: Option<f64>,
+    // Lens radius, zero gives a pinhole camera with everything in focus
+    pub aperture: f64,
+    pub focus_distance: f64,
+    // Number of aperture blades, zero gives a round lens
+    pub blades: usize,
+    pub blade_rotation: f64,
+    forward: Vector,
+    right: Vector,
+    true_up: Vector,
+    tan_half_fov: f64
+}
+
+impl Camera {
+    pub fn new(position: Vector, target: Vector, up: Vector, fov: f64, aspect_ratio: Option<f64>) -> Camera {
+        let forward = (target - position).normalize();
+        let mut right = up.cross(forward);
+        if right.length_squared() < 1.0e-12 {
+            // Looking straight along the up vector, so pick any perpendicular axis
+            right = Vector::new(0.0, 0.0, 1.0).cross(forward);
+            if right.length_squared() < 1.0e-12 {
+                right = Vector::new(1.0, 0.0, 0.0).cross(forward);
+            }
+        }
+        let right = right.normalize();
+        let true_up = forward.cross(right).normalize();
+
+        Camera {
+            position: position,
+            target: target,
+            up: up,
+            fov: fov,
+            aspect_ratio: aspect_ratio,
+            aperture: 0.0,
+            focus_distance: (target - position).length(),
+            blades: 0,
+            blade_rotation: 0.0,
+            forward: forward,
+            right: right,
+            true_up: true_up,
+            tan_half_fov: (fov.to_radians() / 2.0).tan()
+        }
+    }
+
+    pub fn look_at(position: Vector, target: Vector) -> Camera {
+        Camera::new(position, target, UP, 31.4, None)
+    }
+
+    // Returns the origin and direction of the ray through the pixel, offset by a fraction of a pixel.
+    // The lens sample picks the point on the aperture the ray passes through
+    pub fn primary_ray(&self, x: usize, y: usize, width: usize, height: usize, pixel_offset: (f64, f64), lens_sample: (f64, f64)) -> (Vector, Vector) {
+        let aspect_ratio = self.aspect_ratio.unwrap_or(width as f64 / height as f64);
+        let u = 2.0 * (x as f64 + pixel_offset.0) / width as f64 - 1.0;
+        let v = 1.0 - 2.0 * (y as f64 + pixel_offset.1) / height as f64;
+
+        let direction = (self.forward +
+            self.right * (u * self.tan_half_fov * aspect_ratio) +
+            self.true_up * (v * self.tan_half_fov)).normalize();
+        if self.aperture <= 0.0 {
+            return (self.position, direction);
+        }
+
+        // Everything on the plane at the focus distance stays sharp
+        let focus_point = self.position + direction * (self.focus_distance / direction.dot(self.forward));
+        let (lens_x, lens_y) = if self.blades >= 3 {
+            sample_polygon(lens_sample.0, lens_sample.1, self.blades, self.blade_rotation)
+        } else {
+            sample_disk(lens_sample.0, lens_sample.1)
+        };
+        let origin = self.position + (self.right * lens_x + self.true_up * lens_y) * self.aperture;
+        (origin, (focus_point - origin).normalize())
+    }
+}
+
+// Concentric mapping of the unit square onto the unit disk
+fn sample_disk(u: f64, v: f64) -> (f64, f64) {
+    let a = 2.0 * u - 1.0;
+    let b = 2.0 * v - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, theta) = if a.abs() > b.abs() {
+        (a, consts::FRAC_PI_4 * (b / a))
+    } else {
+        (b, consts::FRAC_PI_2 - consts::FRAC_PI_4 * (a / b))
+    };
+    (radius * theta.cos(), radius * theta.sin())
+}
+
+// Uniformly samples a regular polygon inscribed in the unit circle by picking one of its wedges
+fn sample_polygon(u: f64, v: f64, sides: usize, rotation: f64) -> (f64, f64) {
+    let scaled = u * sides as f64;
+    let wedge = (scaled as usize).min(sides - 1);
+    let u = scaled - wedge as f64;
+
+    let angle = 2.0 * consts::PI / sides as f64;
+    let start = rotation + angle * wedge as f64;
+    let end = start + angle;
+
+    let s = u.sqrt();
+    let x = s * ((1.0 - v) * start.cos() + v * end.cos());
+    let y = s * ((1.0 - v) * start.sin() + v * end.sin());
+    (x, y)
+}
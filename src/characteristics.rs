@@ -18,6 +18,16 @@ impl Characteristics {
         }
     }
 
+    // Mixes every property, used where surfaces blend into each other
+    pub fn interpolate(a: Characteristics, b: Characteristics, amount: f64) -> Characteristics {
+        Characteristics {
+            color: Vector::interpolate(a.color, b.color, amount),
+            roughness: a.roughness * (1.0 - amount) + b.roughness * amount,
+            reflectance: a.reflectance * (1.0 - amount) + b.reflectance * amount,
+            absorbance: a.absorbance * (1.0 - amount) + b.absorbance * amount
+        }
+    }
+
     pub fn mirror(color: Vector) -> Characteristics {
         Characteristics {
             color: color,
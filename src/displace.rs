@@ -0,0 +1,54 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use noise::*;
+use aabb::*;
+
+// Offsets the surface of a field by noise, positive noise pushes the surface inwards
+pub struct Displace<T: Field> {
+    pub field: T,
+    pub noise: Noise,
+    pub amplitude: f64
+}
+
+impl<T: Field> Displace<T> {
+    pub fn new(scene: Scene<T>, noise: Noise, amplitude: f64) -> Scene<Displace<T>> {
+        Scene {
+            field: Displace {
+                field: scene.field,
+                noise: noise,
+                amplitude: amplitude
+            }
+        }
+    }
+}
+
+impl<T: Field> Field for Displace<T> {
+    fn bounds(&self) -> Aabb {
+        self.field.bounds().expand(self.amplitude.abs() * self.noise.max_value())
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        let dist = self.field.distance(pos);
+
+        // The noise can't move the surface further than its largest value, so far away the child's
+        // distance minus that is a full speed step. Negative amplitudes push the surface the other
+        // way just as far
+        let amplitude = self.amplitude.abs();
+        let reach = amplitude * self.noise.max_value();
+        if dist > 2.0 * reach {
+            return dist - reach;
+        }
+
+        // Closer in, the sum changes faster than distance does, so the step shrinks to stay safe. The
+        // gradient noises only have sampled slope bounds, so where their true slope is steeper the
+        // marcher can still overshoot a little
+        let lipschitz = 1.0 + amplitude * self.noise.lipschitz();
+        ((dist + self.amplitude * self.noise.sample(pos)) / lipschitz).max(dist - reach)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(pos)
+    }
+}
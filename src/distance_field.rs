@@ -3,14 +3,108 @@ use scene::*;
 use characteristics::*;
 use geometry::*;
 use std::f64::*;
+use std::sync::Arc;
+use ray_marcher::*;
+use interval::*;
+use ray::*;
+use aabb::*;
+use bvh::*;
 
 pub trait Field {
-    fn ray_cast(&self, Vector, Vector) -> Option<Vector>;
+    // Fields without an analytic intersection fall back to sphere tracing their distance
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        RayMarcher::default().march(self, ray).map(|t| Hit::from_field(self, ray, t))
+    }
+
+    // Spans along the ray that lie inside the field, which is how CSG operators combine fields
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        RayMarcher::default().intervals(self, ray)
+    }
+
+    // Box the field's surface and inside lie within, infinite unless the field knows better
+    fn bounds(&self) -> Aabb {
+        Aabb::infinite()
+    }
+
     fn distance(&self, Vector) -> f64;
-    fn normal(&self, Vector) -> Vector;
+
+    // Fields without an analytic normal fall back to the gradient of their distance. Epsilon is the
+    // step to take, callers grow it with distance from the camera so far surfaces don't alias
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        numerical_normal(self, pos, epsilon)
+    }
+
     fn characteristics(&self, Vector) -> Characteristics;
 }
 
+// Gradient of the distance sampled at the corners of a tetrahedron, four evaluations instead of the
+// six central differences take
+pub fn numerical_normal<T: Field + ?Sized>(field: &T, pos: Vector, epsilon: f64) -> Vector {
+    let corners = [
+        Vector::new(1.0, -1.0, -1.0),
+        Vector::new(-1.0, -1.0, 1.0),
+        Vector::new(-1.0, 1.0, -1.0),
+        Vector::new(1.0, 1.0, 1.0)
+    ];
+    let mut gradient = Vector::zero();
+    for &corner in corners.iter() {
+        gradient = gradient + corner * field.distance(pos + corner * epsilon);
+    }
+    gradient.normalize()
+}
+
+impl<T: Field + ?Sized> Field for Box<T> {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        (**self).ray_cast(ray)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        (**self).intervals(ray)
+    }
+
+    fn bounds(&self) -> Aabb {
+        (**self).bounds()
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        (**self).distance(pos)
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        (**self).normal(pos, epsilon)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        (**self).characteristics(pos)
+    }
+}
+
+impl<T: Field + ?Sized> Field for Arc<T> {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        (**self).ray_cast(ray)
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        (**self).intervals(ray)
+    }
+
+    fn bounds(&self) -> Aabb {
+        (**self).bounds()
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        (**self).distance(pos)
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        (**self).normal(pos, epsilon)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        (**self).characteristics(pos)
+    }
+}
+
 pub struct Sphere {
     pub position: Vector,
     pub radius: f64,
@@ -30,18 +124,39 @@ impl Sphere {
 }
 
 impl Field for Sphere {
-    fn ray_cast(&self, pos: Vector, dir: Vector) -> Option<Vector> {
-        if (pos - self.position).length() < self.radius {
-            return Some(pos);
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let t = if (ray.origin - self.position).length() < self.radius {
+            0.0
+        } else {
+            match sphere_span(self.position, self.radius, ray.origin, ray.direction) {
+                Some((start, _)) if start >= 0.0 => start,
+                _ => return None
+            }
+        };
+
+        Some(self.hit(ray, t))
+    }
+
+    // Rays starting inside enter the sphere without crossing its surface
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        match sphere_span(self.position, self.radius, ray.origin, ray.direction) {
+            Some((start, end)) if end > 0.0 => {
+                let enter = if start > 0.0 { Some(self.hit(ray, start)) } else { None };
+                vec![Interval::new(start.max(0.0), end).with_hits(enter, Some(self.hit(ray, end)))]
+            },
+            _ => Vec::new()
         }
-        sphere_intersection(self.position, self.radius, pos, dir)
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.position, Vector::one() * self.radius)
     }
 
     fn distance(&self, pos: Vector) -> f64 {
         (pos - self.position).length() - self.radius
     }
 
-    fn normal(&self, pos: Vector) -> Vector {
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
         (pos - self.position).normalize()
     }
 
@@ -50,6 +165,26 @@ impl Field for Sphere {
     }
 }
 
+impl Sphere {
+    fn hit(&self, ray: &Ray, t: f64) -> Hit {
+        let position = ray.at(t);
+        let normal = self.normal(position, 0.0);
+        // Longitude around the y axis and latitude from the south pole
+        let u = 0.5 + normal.z.atan2(normal.x) / (2.0 * consts::PI);
+        let v = 0.5 + normal.y.max(-1.0).min(1.0).asin() / consts::PI;
+        Hit {
+            t: t,
+            position: position,
+            geometric_normal: normal,
+            shading_normal: normal,
+            uv: (u, v),
+            front_face: ray.direction.dot(normal) < 0.0,
+            object_id: 0,
+            material: self.characteristics
+        }
+    }
+}
+
 pub struct Plane {
     pub normal: Vector,
     pub point: Vector,
@@ -69,12 +204,32 @@ impl Plane {
 }
 
 impl Field for Plane {
-    fn ray_cast(&self, pos: Vector, dir: Vector) -> Option<Vector> {
-        let dist = self.distance(pos);
-        if dist < 0.0 {
-            Some(pos)
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let t = if self.distance(ray.origin) < 0.0 {
+            0.0
         } else {
-            plane_intersection(self.normal, self.point, pos, dir)
+            match plane_intersection(self.normal, self.point, ray.origin, ray.direction) {
+                Some(t) => t,
+                None => return None
+            }
+        };
+
+        Some(self.hit(ray, t))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let dist = self.distance(ray.origin);
+        let denom = self.normal.dot(ray.direction);
+        if denom == 0.0 {
+            return if dist < 0.0 { vec![Interval::whole_ray()] } else { Vec::new() };
+        }
+
+        let t = -dist / denom;
+        if denom > 0.0 {
+            if t > 0.0 { vec![Interval::new(0.0, t).with_hits(None, Some(self.hit(ray, t)))] } else { Vec::new() }
+        } else {
+            let enter = if t > 0.0 { Some(self.hit(ray, t)) } else { None };
+            vec![Interval::new(t.max(0.0), INFINITY).with_hits(enter, None)]
         }
     }
 
@@ -82,7 +237,7 @@ impl Field for Plane {
         (pos - self.point).dot(self.normal)
     }
 
-    fn normal(&self, pos: Vector) -> Vector {
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
         self.normal
     }
 
@@ -91,21 +246,64 @@ impl Field for Plane {
     }
 }
 
+impl Plane {
+    fn hit(&self, ray: &Ray, t: f64) -> Hit {
+        // Coordinates along two directions in the plane, repeating every unit
+        let position = ray.at(t);
+        let tangent = if self.normal.x.abs() < 0.9 { Vector::new(1.0, 0.0, 0.0) } else { Vector::new(0.0, 1.0, 0.0) };
+        let u_axis = self.normal.cross(tangent).normalize();
+        let v_axis = self.normal.cross(u_axis);
+        let local = position - self.point;
+        let (u, v) = (local.dot(u_axis), local.dot(v_axis));
+        Hit {
+            t: t,
+            position: position,
+            geometric_normal: self.normal,
+            shading_normal: self.normal,
+            uv: (u - u.floor(), v - v.floor()),
+            front_face: ray.direction.dot(self.normal) < 0.0,
+            object_id: 0,
+            material: self.characteristics
+        }
+    }
+}
+
 pub struct Negate<T: Field> {
-    pub field: T
+    pub field: T,
+    field_bounds: Aabb
+}
+
+impl<T: Field> Negate<T> {
+    pub fn new(scene: Scene<T>) -> Scene<Negate<T>> {
+        let field_bounds = scene.field.bounds();
+        Scene {
+            field: Negate {
+                field: scene.field,
+                field_bounds: field_bounds
+            }
+        }
+    }
 }
 
 impl<T: Field> Field for Negate<T> {
-    fn ray_cast(&self, pos: Vector, dir: Vector) -> Option<Vector> {
-        self.field.ray_cast(pos, dir)
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        Hit::first_entry(self, ray, &self.intervals(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        // A ray that misses the field is outside it all the way
+        if self.field_bounds.clip(ray).is_none() {
+            return vec![Interval::whole_ray()];
+        }
+        complement_intervals(&self.field.intervals(ray))
     }
 
     fn distance(&self, pos: Vector) -> f64 {
         -self.field.distance(pos)
     }
 
-    fn normal(&self, pos: Vector) -> Vector {
-        -self.field.normal(pos)
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        -self.field.normal(pos, epsilon)
     }
 
     fn characteristics(&self, pos: Vector) -> Characteristics {
@@ -113,45 +311,119 @@ impl<T: Field> Field for Negate<T> {
     }
 }
 
+// Whichever hit is closer, preferring the second on a tie
+fn closer_hit(hit1: Option<Hit>, hit2: Option<Hit>) -> Option<Hit> {
+    let t1 = hit1.map_or(INFINITY, |hit| hit.t);
+    let t2 = hit2.map_or(INFINITY, |hit| hit.t);
+    if t1 < t2 {
+        hit1
+    } else {
+        hit2
+    }
+}
+
 pub struct Union<T1: Field, T2: Field> {
     pub field1: T1,
-    pub field2: T2
+    pub field2: T2,
+    bounds1: Aabb,
+    bounds2: Aabb
 }
 
-impl<T1: Field, T2: Field> Field for Union<T1, T2> {
-    fn ray_cast(&self, pos: Vector, dir: Vector) -> Option<Vector> {
-        let p1 = self.field1.ray_cast(pos, dir);
-        let p2 = self.field2.ray_cast(pos, dir);
-        let dist1 = p1.map_or(INFINITY, |p| (p - pos).length_squared());
-        let dist2 = p2.map_or(INFINITY, |p| (p - pos).length_squared());
-
-        if dist1 < dist2 {
-            p1
+impl<T1: Field, T2: Field> Union<T1, T2> {
+    pub fn new(scene1: Scene<T1>, scene2: Scene<T2>) -> Scene<Union<T1, T2>> {
+        let (bounds1, bounds2) = (scene1.field.bounds(), scene2.field.bounds());
+        Scene {
+            field: Union {
+                field1: scene1.field,
+                field2: scene2.field,
+                bounds1: bounds1,
+                bounds2: bounds2
+            }
+        }
+    }
+
+    // Whether the first field is the closer one, along with the union's distance. A field is never
+    // closer than its box, so the farther box is only looked into when it's closer than the nearer field
+    fn closest(&self, pos: Vector) -> (bool, f64) {
+        let box1 = self.bounds1.distance(pos);
+        let box2 = self.bounds2.distance(pos);
+        if box1 <= box2 {
+            let dist1 = self.field1.distance(pos);
+            if box2 > 0.0 && box2 >= dist1 {
+                return (true, dist1);
+            }
+            let dist2 = self.field2.distance(pos);
+            (dist1 < dist2, dist1.min(dist2))
         } else {
-            p2
+            let dist2 = self.field2.distance(pos);
+            if box1 > 0.0 && box1 >= dist2 {
+                return (false, dist2);
+            }
+            let dist1 = self.field1.distance(pos);
+            (dist1 < dist2, dist1.min(dist2))
         }
     }
+}
 
-    fn distance(&self, pos: Vector) -> f64 {
-        self.field1.distance(pos).min(self.field2.distance(pos))
+impl<T1: Field, T2: Field> Field for Union<T1, T2> {
+    // Casts into the nearer box first, and skips the other if the hit comes before it
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let inverse_direction = Vector::one() / ray.direction;
+        let span1 = self.bounds1.ray_span(ray, inverse_direction);
+        let span2 = self.bounds2.ray_span(ray, inverse_direction);
+        match (span1, span2) {
+            (Some((enter1, _)), Some((enter2, _))) => {
+                if enter1 <= enter2 {
+                    let hit1 = self.field1.ray_cast(ray);
+                    if hit1.map_or(false, |hit| hit.t < enter2) {
+                        return hit1;
+                    }
+                    closer_hit(hit1, self.field2.ray_cast(ray))
+                } else {
+                    let hit2 = self.field2.ray_cast(ray);
+                    if hit2.map_or(false, |hit| hit.t < enter1) {
+                        return hit2;
+                    }
+                    closer_hit(self.field1.ray_cast(ray), hit2)
+                }
+            },
+            (Some(_), None) => self.field1.ray_cast(ray),
+            (None, Some(_)) => self.field2.ray_cast(ray),
+            (None, None) => None
+        }
     }
 
-    fn normal(&self, pos: Vector) -> Vector {
-        let dist1 = self.field1.distance(pos);
-        let dist2 = self.field2.distance(pos);
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let inverse_direction = Vector::one() / ray.direction;
+        let intervals1 = match self.bounds1.ray_span(ray, inverse_direction) {
+            Some(_) => self.field1.intervals(ray),
+            None => Vec::new()
+        };
+        let intervals2 = match self.bounds2.ray_span(ray, inverse_direction) {
+            Some(_) => self.field2.intervals(ray),
+            None => Vec::new()
+        };
+        union_intervals(&intervals1, &intervals2)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds1.union(self.bounds2)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.closest(pos).1
+    }
 
-        if dist1 < dist2 {
-            self.field1.normal(pos)
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        if self.closest(pos).0 {
+            self.field1.normal(pos, epsilon)
         } else {
-            self.field2.normal(pos)
+            self.field2.normal(pos, epsilon)
         }
     }
 
     fn characteristics(&self, pos: Vector) -> Characteristics {
-        let dist1 = self.field1.distance(pos);
-        let dist2 = self.field2.distance(pos);
-
-        if dist1 < dist2 {
+        if self.closest(pos).0 {
             self.field1.characteristics(pos)
         } else {
             self.field2.characteristics(pos)
@@ -161,35 +433,55 @@ impl<T1: Field, T2: Field> Field for Union<T1, T2> {
 
 pub struct Intersection<T1: Field, T2: Field> {
     pub field1: T1,
-    pub field2: T2
+    pub field2: T2,
+    bounds: Aabb
+}
+
+impl<T1: Field, T2: Field> Intersection<T1, T2> {
+    pub fn new(scene1: Scene<T1>, scene2: Scene<T2>) -> Scene<Intersection<T1, T2>> {
+        let bounds = scene1.field.bounds().intersection(scene2.field.bounds());
+        Scene {
+            field: Intersection {
+                field1: scene1.field,
+                field2: scene2.field,
+                bounds: bounds
+            }
+        }
+    }
 }
 
 impl<T1: Field, T2: Field> Field for Intersection<T1, T2> {
-    fn ray_cast(&self, pos: Vector, dir: Vector) -> Option<Vector> {
-        let p1 = self.field1.ray_cast(pos, dir);
-        let p2 = self.field2.ray_cast(pos, dir);
-        let dist1 = p1.map_or(INFINITY, |p| (p - pos).length_squared());
-        let dist2 = p2.map_or(INFINITY, |p| (p - pos).length_squared());
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        Hit::first_entry(self, ray, &self.intervals(ray))
+    }
 
-        if dist1 > dist2 {
-            p1
-        } else {
-            p2
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        if self.bounds.clip(ray).is_none() {
+            return Vec::new();
         }
+        let intervals1 = self.field1.intervals(ray);
+        if intervals1.is_empty() {
+            return intervals1;
+        }
+        intersect_intervals(&intervals1, &self.field2.intervals(ray))
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
     }
 
     fn distance(&self, pos: Vector) -> f64 {
         self.field1.distance(pos).max(self.field2.distance(pos))
     }
 
-    fn normal(&self, pos: Vector) -> Vector {
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
         let dist1 = self.field1.distance(pos);
         let dist2 = self.field2.distance(pos);
 
         if dist1 > dist2 {
-            self.field1.normal(pos)
+            self.field1.normal(pos, epsilon)
         } else {
-            self.field2.normal(pos)
+            self.field2.normal(pos, epsilon)
         }
     }
 
@@ -204,3 +496,363 @@ impl<T1: Field, T2: Field> Field for Intersection<T1, T2> {
         }
     }
 }
+
+// Carves the second field out of the first
+pub struct Difference<T1: Field, T2: Field> {
+    pub field1: T1,
+    pub field2: T2,
+    // Gives the carved surface the second field's characteristics instead of the first's
+    pub carve_characteristics: bool,
+    bounds1: Aabb,
+    bounds2: Aabb
+}
+
+impl<T1: Field, T2: Field> Difference<T1, T2> {
+    pub fn new(scene1: Scene<T1>, scene2: Scene<T2>, carve_characteristics: bool) -> Scene<Difference<T1, T2>> {
+        let (bounds1, bounds2) = (scene1.field.bounds(), scene2.field.bounds());
+        Scene {
+            field: Difference {
+                field1: scene1.field,
+                field2: scene2.field,
+                carve_characteristics: carve_characteristics,
+                bounds1: bounds1,
+                bounds2: bounds2
+            }
+        }
+    }
+
+    fn is_carved(&self, pos: Vector) -> bool {
+        -self.field2.distance(pos) > self.field1.distance(pos)
+    }
+}
+
+impl<T1: Field, T2: Field> Field for Difference<T1, T2> {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        Hit::first_entry(self, ray, &self.intervals(ray))
+    }
+
+    // Rays that miss the carved field only see the first one
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let inverse_direction = Vector::one() / ray.direction;
+        if self.bounds1.ray_span(ray, inverse_direction).is_none() {
+            return Vec::new();
+        }
+        let intervals1 = self.field1.intervals(ray);
+        if intervals1.is_empty() || self.bounds2.ray_span(ray, inverse_direction).is_none() {
+            return intervals1;
+        }
+        let mut intervals2 = self.field2.intervals(ray);
+        if !self.carve_characteristics {
+            for interval in &mut intervals2 {
+                for hit in interval.enter.iter_mut().chain(interval.exit.iter_mut()) {
+                    hit.material = self.field1.characteristics(hit.position);
+                }
+            }
+        }
+        subtract_intervals(&intervals1, &intervals2)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds1
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field1.distance(pos).max(-self.field2.distance(pos))
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        if self.is_carved(pos) {
+            -self.field2.normal(pos, epsilon)
+        } else {
+            self.field1.normal(pos, epsilon)
+        }
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        if self.carve_characteristics && self.is_carved(pos) {
+            self.field2.characteristics(pos)
+        } else {
+            self.field1.characteristics(pos)
+        }
+    }
+}
+
+// Keeps the fields with finite bounds in a bounding volume hierarchy, so rays and distance queries
+// only visit the fields near them
+pub struct UnionN {
+    pub fields: Vec<Box<dyn Field + Send + Sync>>,
+    bvh: Bvh,
+    // Index into the fields for each item of the hierarchy
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>
+}
+
+impl UnionN {
+    pub fn new(fields: Vec<Box<dyn Field + Send + Sync>>) -> Scene<UnionN> {
+        let mut bounds = Vec::new();
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            let field_bounds = field.bounds();
+            if field_bounds.is_finite() {
+                bounds.push(field_bounds);
+                bounded.push(index);
+            } else {
+                unbounded.push(index);
+            }
+        }
+        Scene {
+            field: UnionN {
+                fields: fields,
+                bvh: Bvh::new(&bounds),
+                bounded: bounded,
+                unbounded: unbounded
+            }
+        }
+    }
+
+    // The closest field along with its distance
+    fn closest_field(&self, pos: Vector) -> Option<(&Box<dyn Field + Send + Sync>, f64)> {
+        let mut closest = None;
+        let mut closest_dist = INFINITY;
+        for &index in &self.unbounded {
+            let dist = self.fields[index].distance(pos);
+            if closest.is_none() || dist < closest_dist {
+                closest = Some(index);
+                closest_dist = dist;
+            }
+        }
+        self.bvh.closest(pos, |item| {
+            let index = self.bounded[item];
+            let dist = self.fields[index].distance(pos);
+            if closest.is_none() || dist < closest_dist {
+                closest = Some(index);
+                closest_dist = dist;
+            }
+            dist
+        });
+        closest.map(|index| (&self.fields[index], closest_dist))
+    }
+}
+
+impl Field for UnionN {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+        for &index in &self.unbounded {
+            if let Some(hit) = self.fields[index].ray_cast(ray) {
+                if closest.map_or(true, |closest| hit.t < closest.t) {
+                    closest = Some(hit);
+                }
+            }
+        }
+        let max_distance = closest.map_or(INFINITY, |hit| hit.t);
+        self.bvh.ray_cast(ray, max_distance, |item, closest_t| {
+            match self.fields[self.bounded[item]].ray_cast(ray) {
+                Some(hit) if hit.t < closest_t => {
+                    closest = Some(hit);
+                    Some(hit.t)
+                },
+                _ => None
+            }
+        });
+        closest
+    }
+
+    // Fields whose boxes the ray misses have no spans along it, so only the rest are merged
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut intervals = Vec::new();
+        for &index in &self.unbounded {
+            intervals = union_intervals(&intervals, &self.fields[index].intervals(ray));
+        }
+        self.bvh.along_ray(ray, |item| {
+            intervals = union_intervals(&intervals, &self.fields[self.bounded[item]].intervals(ray));
+        });
+        intervals
+    }
+
+    fn bounds(&self) -> Aabb {
+        if self.unbounded.is_empty() {
+            self.bvh.bounds()
+        } else {
+            Aabb::infinite()
+        }
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.closest_field(pos).map_or(INFINITY, |(_, dist)| dist)
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        self.closest_field(pos).map_or(Vector::zero(), |(field, _)| field.normal(pos, epsilon))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.closest_field(pos).map_or(Characteristics::default(), |(field, _)| field.characteristics(pos))
+    }
+}
+
+// An empty IntersectionN is treated as empty space rather than all of space
+pub struct IntersectionN {
+    pub fields: Vec<Box<dyn Field + Send + Sync>>,
+    bounds: Aabb
+}
+
+impl IntersectionN {
+    pub fn new(fields: Vec<Box<dyn Field + Send + Sync>>) -> Scene<IntersectionN> {
+        let bounds = if fields.is_empty() {
+            Aabb::empty()
+        } else {
+            fields.iter().fold(Aabb::infinite(), |bounds, field| bounds.intersection(field.bounds()))
+        };
+        Scene {
+            field: IntersectionN {
+                fields: fields,
+                bounds: bounds
+            }
+        }
+    }
+
+    fn farthest_field(&self, pos: Vector) -> Option<&Box<dyn Field + Send + Sync>> {
+        let mut farthest = None;
+        let mut farthest_dist = NEG_INFINITY;
+        for field in &self.fields {
+            let dist = field.distance(pos);
+            if farthest.is_none() || dist > farthest_dist {
+                farthest = Some(field);
+                farthest_dist = dist;
+            }
+        }
+        farthest
+    }
+}
+
+impl Field for IntersectionN {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        Hit::first_entry(self, ray, &self.intervals(ray))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        if self.bounds.clip(ray).is_none() {
+            return Vec::new();
+        }
+        let mut fields = self.fields.iter();
+        let mut intervals = match fields.next() {
+            Some(field) => field.intervals(ray),
+            None => return Vec::new()
+        };
+        for field in fields {
+            if intervals.is_empty() {
+                break;
+            }
+            intervals = intersect_intervals(&intervals, &field.intervals(ray));
+        }
+        intervals
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        if self.fields.is_empty() {
+            return INFINITY;
+        }
+        self.fields.iter().fold(NEG_INFINITY, |dist, field| dist.max(field.distance(pos)))
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        self.farthest_field(pos).map_or(Vector::zero(), |field| field.normal(pos, epsilon))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.farthest_field(pos).map_or(Characteristics::default(), |field| field.characteristics(pos))
+    }
+}
+
+// Tags the hits on a field with an id, so the renderer can tell objects apart. Ids given further in
+// take precedence
+pub struct Identified<T: Field> {
+    pub field: T,
+    pub id: usize
+}
+
+impl<T: Field> Identified<T> {
+    pub fn new(scene: Scene<T>, id: usize) -> Scene<Identified<T>> {
+        Scene {
+            field: Identified {
+                field: scene.field,
+                id: id
+            }
+        }
+    }
+}
+
+impl<T: Field> Field for Identified<T> {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        self.field.ray_cast(ray).map(|hit| {
+            if hit.object_id == 0 {
+                Hit { object_id: self.id, ..hit }
+            } else {
+                hit
+            }
+        })
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let tag = |hit: Hit| if hit.object_id == 0 { Hit { object_id: self.id, ..hit } } else { hit };
+        self.field.intervals(ray).iter()
+            .map(|interval| interval.with_hits(interval.enter.map(&tag), interval.exit.map(&tag)))
+            .collect()
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.field.bounds()
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(pos)
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        self.field.normal(pos, epsilon)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_n_intervals_match_merging_every_field() {
+        let mut fields: Vec<Box<dyn Field + Send + Sync>> = Vec::new();
+        for i in 0..60 {
+            let angle = i as f64 * 0.9;
+            let center = Vector::new(angle.cos() * 6.0, (angle * 0.4).sin() * 4.0, angle.sin() * 6.0);
+            fields.push(Box::new(Sphere::new(center, 0.5 + (i % 4) as f64 * 0.3, Characteristics::default()).field));
+        }
+        fields.push(Box::new(Plane::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, -5.0, 0.0), Characteristics::default()).field));
+
+        let merged = |ray: &Ray| fields.iter().fold(Vec::new(), |intervals, field| union_intervals(&intervals, &field.intervals(ray)));
+        let expected: Vec<Vec<(f64, f64)>> = (0..200)
+            .map(|i| ray(i))
+            .map(|ray| merged(&ray).iter().map(|interval| (interval.start, interval.end)).collect())
+            .collect();
+
+        let union = UnionN::new(fields).field;
+        for i in 0..200 {
+            let spans: Vec<(f64, f64)> = union.intervals(&ray(i)).iter().map(|interval| (interval.start, interval.end)).collect();
+            assert_eq!(spans, expected[i]);
+        }
+    }
+
+    fn ray(index: usize) -> Ray {
+        let angle = index as f64 * 0.31;
+        let origin = Vector::new(angle.cos() * 12.0, (angle * 1.7).sin() * 3.0, angle.sin() * 12.0);
+        let target = Vector::new((angle * 2.3).sin() * 5.0, (angle * 0.9).cos() * 2.0, (angle * 1.1).cos() * 5.0);
+        Ray::new(origin, (target - origin).normalize())
+    }
+}
@@ -0,0 +1,404 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use aabb::*;
+use std::f64::*;
+
+// Domain operators remap the point before asking the wrapped field, so one field can stand for many
+// copies or a deformed shape. Distances stay valid as long as the remapping doesn't stretch space;
+// the deformations that do divide by how much they stretch it
+
+fn rotate_y(v: Vector, angle: f64) -> Vector {
+    let (s, c) = angle.sin_cos();
+    Vector::new(c * v.x - s * v.z, v.y, s * v.x + c * v.z)
+}
+
+fn rotate_z(v: Vector, angle: f64) -> Vector {
+    let (s, c) = angle.sin_cos();
+    Vector::new(c * v.x - s * v.y, s * v.x + c * v.y, v.z)
+}
+
+// Largest stretch of a rotation whose angle changes by rate per unit along its own axis, at the given
+// distance from the axis. The jacobian is a rotation plus a shear of rate * radius perpendicular to
+// the axis, which stretches by less than the shear plus one
+fn twist_stretch(rate: f64, radius: f64) -> f64 {
+    let shear = rate.abs() * radius;
+    (shear + (shear * shear + 4.0).sqrt()) / 2.0
+}
+
+// Box around everything the given box sweeps out turning around the y axis (1) or the z axis (2). The
+// farthest point from the axis is always one of the corners
+fn swept_bounds(bounds: Aabb, axis: usize) -> Aabb {
+    if bounds.is_empty() {
+        return bounds;
+    }
+    if !bounds.is_finite() {
+        return Aabb::infinite();
+    }
+    let radius = bounds.corners().iter()
+        .map(|corner| {
+            let length_squared = corner.length_squared() - corner.component(axis) * corner.component(axis);
+            length_squared.max(0.0).sqrt()
+        })
+        .fold(0.0, f64::max);
+    if axis == 1 {
+        Aabb::new(Vector::new(-radius, bounds.min.y, -radius), Vector::new(radius, bounds.max.y, radius))
+    } else {
+        Aabb::new(Vector::new(-radius, -radius, bounds.min.z), Vector::new(radius, radius, bounds.max.z))
+    }
+}
+
+// Copies the field every period along each axis, a zero period leaves that axis alone. The field
+// should be centered on the origin and fit inside one cell, otherwise distances near the cell
+// borders can overshoot
+pub struct Repeat<T: Field> {
+    pub field: T,
+    pub period: Vector,
+    // Number of copies on each side of the original along each axis, infinite for endless repetition
+    pub limit: Vector
+}
+
+impl<T: Field> Repeat<T> {
+    pub fn new(scene: Scene<T>, period: Vector) -> Scene<Repeat<T>> {
+        Repeat::limited(scene, period, Vector::one() * INFINITY)
+    }
+
+    pub fn limited(scene: Scene<T>, period: Vector, limit: Vector) -> Scene<Repeat<T>> {
+        Scene {
+            field: Repeat {
+                field: scene.field,
+                period: period,
+                limit: limit
+            }
+        }
+    }
+
+    fn local(&self, pos: Vector) -> Vector {
+        let cell = |p: f64, period: f64, limit: f64| {
+            if period == 0.0 {
+                0.0
+            } else {
+                (p / period).round().max(-limit).min(limit) * period
+            }
+        };
+        pos - Vector::new(
+            cell(pos.x, self.period.x, self.limit.x),
+            cell(pos.y, self.period.y, self.limit.y),
+            cell(pos.z, self.period.z, self.limit.z))
+    }
+}
+
+impl<T: Field> Field for Repeat<T> {
+    // The copies stretch limit periods out from the original on each repeated axis
+    fn bounds(&self) -> Aabb {
+        let bounds = self.field.bounds();
+        if bounds.is_empty() {
+            return bounds;
+        }
+        let reach = |period: f64, limit: f64| if period == 0.0 { 0.0 } else { limit * period.abs() };
+        let reach = Vector::new(
+            reach(self.period.x, self.limit.x),
+            reach(self.period.y, self.limit.y),
+            reach(self.period.z, self.limit.z));
+        Aabb::new(bounds.min - reach, bounds.max + reach)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(self.local(pos))
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        self.field.normal(self.local(pos), epsilon)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(self.local(pos))
+    }
+}
+
+// Copies the field count times around the y axis. The original should sit in the sector around the
+// positive x axis
+pub struct PolarRepeat<T: Field> {
+    pub field: T,
+    pub count: usize
+}
+
+impl<T: Field> PolarRepeat<T> {
+    pub fn new(scene: Scene<T>, count: usize) -> Scene<PolarRepeat<T>> {
+        Scene {
+            field: PolarRepeat {
+                field: scene.field,
+                count: count
+            }
+        }
+    }
+
+    // Angle of the sector the point falls in
+    fn sector_angle(&self, pos: Vector) -> f64 {
+        let sector = 2.0 * consts::PI / self.count.max(1) as f64;
+        (pos.z.atan2(pos.x) / sector).round() * sector
+    }
+}
+
+impl<T: Field> Field for PolarRepeat<T> {
+    fn bounds(&self) -> Aabb {
+        swept_bounds(self.field.bounds(), 1)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(rotate_y(pos, -self.sector_angle(pos)))
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let angle = self.sector_angle(pos);
+        rotate_y(self.field.normal(rotate_y(pos, -angle), epsilon), angle)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(rotate_y(pos, -self.sector_angle(pos)))
+    }
+}
+
+// Reflects the half of the field on the normal's side of the plane onto the other side
+pub struct Mirror<T: Field> {
+    pub field: T,
+    pub normal: Vector,
+    pub point: Vector
+}
+
+impl<T: Field> Mirror<T> {
+    pub fn new(scene: Scene<T>, normal: Vector, point: Vector) -> Scene<Mirror<T>> {
+        Scene {
+            field: Mirror {
+                field: scene.field,
+                normal: normal.normalize(),
+                point: point
+            }
+        }
+    }
+
+    fn local(&self, pos: Vector) -> (Vector, bool) {
+        let side = (pos - self.point).dot(self.normal);
+        if side < 0.0 {
+            (pos - self.normal * (2.0 * side), true)
+        } else {
+            (pos, false)
+        }
+    }
+}
+
+impl<T: Field> Field for Mirror<T> {
+    // The field's box along with its reflection
+    fn bounds(&self) -> Aabb {
+        let bounds = self.field.bounds();
+        if bounds.is_empty() {
+            return bounds;
+        }
+        if !bounds.is_finite() {
+            return Aabb::infinite();
+        }
+        bounds.corners().iter().fold(bounds, |reflected, &corner| {
+            let side = (corner - self.point).dot(self.normal);
+            reflected.grow(corner - self.normal * (2.0 * side))
+        })
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(self.local(pos).0)
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let (local, folded) = self.local(pos);
+        let normal = self.field.normal(local, epsilon);
+        if folded {
+            normal - self.normal * (2.0 * normal.dot(self.normal))
+        } else {
+            normal
+        }
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(self.local(pos).0)
+    }
+}
+
+// Folds space across the chosen axis planes through the origin, so the part of the field in the
+// positive octant is reflected into all the others
+pub struct Symmetry<T: Field> {
+    pub field: T,
+    pub x: bool,
+    pub y: bool,
+    pub z: bool
+}
+
+impl<T: Field> Symmetry<T> {
+    pub fn new(scene: Scene<T>, x: bool, y: bool, z: bool) -> Scene<Symmetry<T>> {
+        Scene {
+            field: Symmetry {
+                field: scene.field,
+                x: x,
+                y: y,
+                z: z
+            }
+        }
+    }
+
+    // Sign per axis that carries the point into the folded space and back
+    fn signs(&self, pos: Vector) -> Vector {
+        let sign = |folded: bool, p: f64| if folded && p < 0.0 { -1.0 } else { 1.0 };
+        Vector::new(sign(self.x, pos.x), sign(self.y, pos.y), sign(self.z, pos.z))
+    }
+}
+
+impl<T: Field> Field for Symmetry<T> {
+    // Folded axes reach as far below zero as the field does above it
+    fn bounds(&self) -> Aabb {
+        let bounds = self.field.bounds();
+        if bounds.is_empty() {
+            return bounds;
+        }
+        let fold = |folded: bool, low: f64, high: f64| if folded { (-high.max(0.0), high.max(0.0)) } else { (low, high) };
+        let (x, y, z) = (
+            fold(self.x, bounds.min.x, bounds.max.x),
+            fold(self.y, bounds.min.y, bounds.max.y),
+            fold(self.z, bounds.min.z, bounds.max.z));
+        Aabb::new(Vector::new(x.0, y.0, z.0), Vector::new(x.1, y.1, z.1))
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(pos * self.signs(pos))
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let signs = self.signs(pos);
+        self.field.normal(pos * signs, epsilon) * signs
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(pos * self.signs(pos))
+    }
+}
+
+// Rotates the field around the y axis by rate radians per unit of height
+pub struct Twist<T: Field> {
+    pub field: T,
+    pub rate: f64
+}
+
+impl<T: Field> Twist<T> {
+    pub fn new(scene: Scene<T>, rate: f64) -> Scene<Twist<T>> {
+        Scene {
+            field: Twist {
+                field: scene.field,
+                rate: rate
+            }
+        }
+    }
+
+    fn local(&self, pos: Vector) -> Vector {
+        rotate_y(pos, -self.rate * pos.y)
+    }
+
+    fn stretch(&self, pos: Vector) -> f64 {
+        twist_stretch(self.rate, (pos.x * pos.x + pos.z * pos.z).sqrt())
+    }
+}
+
+impl<T: Field> Field for Twist<T> {
+    fn bounds(&self) -> Aabb {
+        swept_bounds(self.field.bounds(), 1)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(self.local(pos)) / self.stretch(pos)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(self.local(pos))
+    }
+}
+
+// Bends the field in the xy plane, turning it around the z axis by rate radians per unit along x
+pub struct Bend<T: Field> {
+    pub field: T,
+    pub rate: f64
+}
+
+impl<T: Field> Bend<T> {
+    pub fn new(scene: Scene<T>, rate: f64) -> Scene<Bend<T>> {
+        Scene {
+            field: Bend {
+                field: scene.field,
+                rate: rate
+            }
+        }
+    }
+
+    fn local(&self, pos: Vector) -> Vector {
+        rotate_z(pos, self.rate * pos.x)
+    }
+
+    // The angle changes along x, which lies in the plane of rotation, so the shear can point along x
+    // itself and stretch space by the full shear plus one
+    fn stretch(&self, pos: Vector) -> f64 {
+        1.0 + self.rate.abs() * (pos.x * pos.x + pos.y * pos.y).sqrt()
+    }
+}
+
+impl<T: Field> Field for Bend<T> {
+    fn bounds(&self) -> Aabb {
+        swept_bounds(self.field.bounds(), 2)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(self.local(pos)) / self.stretch(pos)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(self.local(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitives::*;
+
+    // Checks the slope of the wrapped field's distance, seen through the deformation, against the
+    // stretch it gets divided by. Slopes are finite differences between nearby points around the field
+    fn check_stretch<F: Fn(Vector) -> f64, S: Fn(Vector) -> f64>(distance: F, stretch: S, extent: Vector) {
+        let step = 1.0e-5;
+        let directions = [
+            Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0),
+            Vector::new(1.0, 1.0, 0.0).normalize(), Vector::new(1.0, -1.0, 0.0).normalize(), Vector::new(1.0, 1.0, 1.0).normalize()
+        ];
+        for i in 0..41 {
+            for j in 0..41 {
+                for k in 0..5 {
+                    let pos = Vector::new(i as f64 / 20.0 - 1.0, j as f64 / 20.0 - 1.0, k as f64 / 2.0 - 1.0) * extent;
+                    for &direction in directions.iter() {
+                        let slope = (distance(pos + direction * step) - distance(pos)).abs() / step;
+                        assert!(slope <= stretch(pos) * (1.0 + 1.0e-4), "slope {} over stretch {} at {:?}", slope, stretch(pos), pos);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bends_stretch_space_by_less_than_their_bound() {
+        for &rate in [0.25, 1.0, -1.5].iter() {
+            let bent = Bend::new(AxisAlignedBox::new(Vector::zero(), Vector::new(3.0, 1.0, 0.5), Characteristics::default()), rate).field;
+            check_stretch(|pos| bent.field.distance(bent.local(pos)), |pos| bent.stretch(pos), Vector::new(4.0, 2.5, 1.0));
+        }
+    }
+
+    #[test]
+    fn twists_stretch_space_by_less_than_their_bound() {
+        for &rate in [0.25, 1.0, -1.5].iter() {
+            let twisted = Twist::new(AxisAlignedBox::new(Vector::zero(), Vector::new(1.0, 3.0, 0.5), Characteristics::default()), rate).field;
+            check_stretch(|pos| twisted.field.distance(twisted.local(pos)), |pos| twisted.stretch(pos), Vector::new(2.0, 4.0, 2.0));
+        }
+    }
+}
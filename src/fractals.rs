@@ -0,0 +1,366 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use aabb::*;
+use std::f64::*;
+
+// Closest the iterated point came to the origin and to each of the axis planes while escaping
+#[derive(Copy, Clone, Debug)]
+pub struct OrbitTrap {
+    pub origin: f64,
+    pub planes: Vector
+}
+
+impl OrbitTrap {
+    pub fn new() -> OrbitTrap {
+        OrbitTrap {
+            origin: INFINITY,
+            planes: Vector::one() * INFINITY
+        }
+    }
+
+    pub fn add(&mut self, z: Vector) {
+        self.origin = self.origin.min(z.length());
+        self.planes = self.planes.min(z.abs());
+    }
+}
+
+// Which part of the orbit trap drives the coloring
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TrapShape {
+    Origin,
+    Planes
+}
+
+// Colors a fractal by how close its orbit came to the trap, points whose orbits pass right by it
+// take the trap color and the rest fade to the base color
+#[derive(Copy, Clone)]
+pub struct TrapColoring {
+    pub characteristics: Characteristics,
+    pub trap_color: Vector,
+    pub shape: TrapShape
+}
+
+impl TrapColoring {
+    pub fn new(characteristics: Characteristics, trap_color: Vector, shape: TrapShape) -> TrapColoring {
+        TrapColoring {
+            characteristics: characteristics,
+            trap_color: trap_color,
+            shape: shape
+        }
+    }
+
+    pub fn characteristics(&self, trap: OrbitTrap) -> Characteristics {
+        let closest = match self.shape {
+            TrapShape::Origin => trap.origin,
+            TrapShape::Planes => trap.planes.x.min(trap.planes.y).min(trap.planes.z)
+        };
+        let mut characteristics = self.characteristics;
+        characteristics.color = Vector::interpolate(self.trap_color, self.characteristics.color, closest.max(0.0).min(1.0));
+        characteristics
+    }
+}
+
+// Powers below 2 reach past the bailout radius, so they aren't bounded by it
+pub struct Mandelbulb {
+    pub center: Vector,
+    pub power: f64,
+    pub iterations: usize,
+    pub coloring: TrapColoring
+}
+
+const MANDELBULB_BAILOUT: f64 = 2.0;
+
+impl Mandelbulb {
+    pub fn new(center: Vector, power: f64, iterations: usize, coloring: TrapColoring) -> Scene<Mandelbulb> {
+        Scene {
+            field: Mandelbulb {
+                center: center,
+                power: power,
+                iterations: iterations,
+                coloring: coloring
+            }
+        }
+    }
+
+    // Returns the distance estimate along with the orbit trap of the iteration
+    pub fn estimate(&self, pos: Vector) -> (f64, OrbitTrap) {
+        let c = pos - self.center;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut trap = OrbitTrap::new();
+
+        // The set lies within the bailout radius, and far from it the estimate overshoots, so the
+        // distance to the bailout sphere is used instead
+        if r > 2.0 * MANDELBULB_BAILOUT {
+            return (r - MANDELBULB_BAILOUT, trap);
+        }
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > MANDELBULB_BAILOUT {
+                break;
+            }
+            trap.add(z);
+
+            // Raise to the power in spherical coordinates, with y as the pole
+            let theta = if r > 0.0 { (z.y / r).acos() } else { 0.0 };
+            let phi = z.z.atan2(z.x);
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            let theta = theta * self.power;
+            let phi = phi * self.power;
+            z = Vector::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) * zr + c;
+        }
+        if r == 0.0 {
+            return (0.0, trap);
+        }
+        (0.5 * r.ln() * r / dr, trap)
+    }
+}
+
+impl Field for Mandelbulb {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, Vector::one() * MANDELBULB_BAILOUT)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.estimate(pos).0
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.coloring.characteristics(self.estimate(pos).1)
+    }
+}
+
+// Box folds and sphere folds repeated, with a negative scale giving a closed shape and a positive
+// scale a box shaped one
+pub struct Mandelbox {
+    pub center: Vector,
+    pub scale: f64,
+    pub iterations: usize,
+    pub coloring: TrapColoring
+}
+
+const MANDELBOX_MIN_RADIUS_SQUARED: f64 = 0.25;
+const MANDELBOX_FIXED_RADIUS_SQUARED: f64 = 1.0;
+// Orbits are stopped once this far out, and never before they leave the set's bounds, so large scales
+// can't overflow to infinity
+const MANDELBOX_BAILOUT: f64 = 1024.0;
+
+impl Mandelbox {
+    pub fn new(center: Vector, scale: f64, iterations: usize, coloring: TrapColoring) -> Scene<Mandelbox> {
+        Scene {
+            field: Mandelbox {
+                center: center,
+                scale: scale,
+                iterations: iterations,
+                coloring: coloring
+            }
+        }
+    }
+
+    // Radius around the center the set stays within. With a negative scale that is 2, with a scale
+    // above one 2 (s + 1) / (s - 1). Scales in between don't give a bounded set
+    fn radius(&self) -> f64 {
+        if self.scale < 0.0 {
+            2.0
+        } else if self.scale > 1.0 {
+            2.0 * (self.scale + 1.0) / (self.scale - 1.0)
+        } else {
+            INFINITY
+        }
+    }
+
+    pub fn estimate(&self, pos: Vector) -> (f64, OrbitTrap) {
+        let c = pos - self.center;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut trap = OrbitTrap::new();
+        let bailout = MANDELBOX_BAILOUT.max(2.0 * self.radius());
+        for _ in 0..self.iterations {
+            // Box fold
+            z = z.max(-Vector::one()).min(Vector::one()) * 2.0 - z;
+
+            // Sphere fold
+            let r2 = z.length_squared();
+            if r2 < MANDELBOX_MIN_RADIUS_SQUARED {
+                let factor = MANDELBOX_FIXED_RADIUS_SQUARED / MANDELBOX_MIN_RADIUS_SQUARED;
+                z = z * factor;
+                dr = dr * factor;
+            } else if r2 < MANDELBOX_FIXED_RADIUS_SQUARED {
+                let factor = MANDELBOX_FIXED_RADIUS_SQUARED / r2;
+                z = z * factor;
+                dr = dr * factor;
+            }
+
+            z = z * self.scale + c;
+            dr = dr * self.scale.abs() + 1.0;
+            trap.add(z);
+            if z.length_squared() > bailout * bailout {
+                break;
+            }
+        }
+        (z.length() / dr, trap)
+    }
+}
+
+impl Field for Mandelbox {
+    fn bounds(&self) -> Aabb {
+        let radius = self.radius();
+        if radius < INFINITY {
+            Aabb::around(self.center, Vector::one() * radius)
+        } else {
+            Aabb::infinite()
+        }
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.estimate(pos).0
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.coloring.characteristics(self.estimate(pos).1)
+    }
+}
+
+// Spans -size to size along every axis
+pub struct MengerSponge {
+    pub center: Vector,
+    pub size: f64,
+    pub iterations: usize,
+    pub coloring: TrapColoring
+}
+
+impl MengerSponge {
+    pub fn new(center: Vector, size: f64, iterations: usize, coloring: TrapColoring) -> Scene<MengerSponge> {
+        Scene {
+            field: MengerSponge {
+                center: center,
+                size: size,
+                iterations: iterations,
+                coloring: coloring
+            }
+        }
+    }
+
+    pub fn estimate(&self, pos: Vector) -> (f64, OrbitTrap) {
+        let p = (pos - self.center) / self.size;
+        let q = p.abs() - Vector::one();
+        let mut dist = q.max(Vector::zero()).length() + q.max_component().min(0.0);
+        let mut trap = OrbitTrap::new();
+
+        // Each level carves a cross shaped hole out of every cell of the previous one
+        let mut scale = 1.0;
+        for _ in 0..self.iterations {
+            let wrap = |v: f64| {
+                let m = v * scale;
+                m - 2.0 * (m / 2.0).floor() - 1.0
+            };
+            let a = Vector::new(wrap(p.x), wrap(p.y), wrap(p.z));
+            scale = scale * 3.0;
+            let r = (Vector::one() - a.abs() * 3.0).abs();
+            trap.add(r);
+
+            let da = r.x.max(r.y);
+            let db = r.y.max(r.z);
+            let dc = r.z.max(r.x);
+            let cross = (da.min(db).min(dc) - 1.0) / scale;
+            dist = dist.max(cross);
+        }
+        (dist * self.size, trap)
+    }
+}
+
+impl Field for MengerSponge {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, Vector::one() * self.size.abs())
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.estimate(pos).0
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.coloring.characteristics(self.estimate(pos).1)
+    }
+}
+
+// Corners at (1, 1, 1), (-1, -1, 1), (-1, 1, -1) and (1, -1, -1) times the size
+pub struct Sierpinski {
+    pub center: Vector,
+    pub size: f64,
+    pub iterations: usize,
+    pub coloring: TrapColoring
+}
+
+impl Sierpinski {
+    pub fn new(center: Vector, size: f64, iterations: usize, coloring: TrapColoring) -> Scene<Sierpinski> {
+        Scene {
+            field: Sierpinski {
+                center: center,
+                size: size,
+                iterations: iterations,
+                coloring: coloring
+            }
+        }
+    }
+
+    pub fn estimate(&self, pos: Vector) -> (f64, OrbitTrap) {
+        let mut z = (pos - self.center) / self.size;
+        let mut trap = OrbitTrap::new();
+        let mut scale = 1.0;
+        for _ in 0..self.iterations {
+            // Reflect into the octant around the (1, 1, 1) corner, then grow that corner's copy
+            // to the full size
+            if z.x + z.y < 0.0 {
+                z = Vector::new(-z.y, -z.x, z.z);
+            }
+            if z.x + z.z < 0.0 {
+                z = Vector::new(-z.z, z.y, -z.x);
+            }
+            if z.y + z.z < 0.0 {
+                z = Vector::new(z.x, -z.z, -z.y);
+            }
+            z = z * 2.0 - Vector::one();
+            scale = scale * 2.0;
+            trap.add(z);
+        }
+
+        let faces = (-z.x - z.y - z.z).max(z.x + z.y - z.z).max(z.x - z.y + z.z).max(-z.x + z.y + z.z);
+        ((faces - 1.0) / 3.0f64.sqrt() / scale * self.size, trap)
+    }
+}
+
+impl Field for Sierpinski {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, Vector::one() * self.size.abs())
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.estimate(pos).0
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.coloring.characteristics(self.estimate(pos).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_mandelbox_scales_stay_finite() {
+        let coloring = TrapColoring::new(Characteristics::default(), Vector::one(), TrapShape::Origin);
+        for &scale in [-1.5, 2.0, 1.0e30, -1.0e30].iter() {
+            let mandelbox = Mandelbox::new(Vector::zero(), scale, 30, coloring).field;
+            for i in 0..100 {
+                let pos = Vector::new(i as f64 * 0.07 - 3.5, (i as f64 * 0.3).sin() * 2.0, (i as f64 * 0.7).cos());
+                let dist = mandelbox.distance(pos);
+                assert!(dist.is_finite(), "scale {} gave {} at {:?}", scale, dist, pos);
+            }
+        }
+    }
+}
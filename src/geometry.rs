@@ -28,14 +28,179 @@ pub fn sphere_intersection(center: Vector, radius: f64, position: Vector, direct
     return Some(position + center + t * direction);
 }
 
-pub fn plane_intersection(normal: Vector, point: Vector, position: Vector, direction: Vector) -> Option<Vector> {
+// Distance along the ray to the plane, hits right at the origin are ignored
+pub fn plane_intersection(normal: Vector, point: Vector, position: Vector, direction: Vector) -> Option<f64> {
     let denom = normal.dot(direction);
     if denom.abs() > 1.0e-6 {
         let difference = point - position;
         let t = difference.dot(normal) / denom;
         if t > 0.0001 {
-            return Some(position + direction * t);
+            return Some(t);
         }
     }
     return None;
 }
+
+// Distances along the ray where it enters and leaves the sphere, the entry may lie behind the origin
+pub fn sphere_span(center: Vector, radius: f64, position: Vector, direction: Vector) -> Option<(f64, f64)> {
+    let l = center - position;
+    let tca = l.dot(direction);
+    let d2 = l.dot(l) - tca * tca;
+    let radius2 = radius * radius;
+    if d2 > radius2 {
+        return None;
+    }
+    let thc = (radius2 - d2).sqrt();
+    Some((tca - thc, tca + thc))
+}
+
+// Watertight ray/triangle intersection from Woop, Benthin and Wald. The triangle is sheared into a
+// space where the ray runs along z, so no ray slips between triangles sharing an edge. Rays right
+// through an edge or vertex are given to one side of it by the top-left rule, so they hit exactly one
+// of the triangles around it when they cross the surface, and none or two when they graze a fold.
+// Returns the distance along the ray and the barycentric weights of the corners
+pub fn triangle_intersection(corners: [Vector; 3], position: Vector, direction: Vector) -> Option<(f64, [f64; 3])> {
+    let abs = direction.abs();
+    let kz = if abs.x > abs.y && abs.x > abs.z { 0 } else if abs.y > abs.z { 1 } else { 2 };
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if direction.component(kz) < 0.0 {
+        let swap = kx;
+        kx = ky;
+        ky = swap;
+    }
+
+    let dz = direction.component(kz);
+    let shear_x = direction.component(kx) / dz;
+    let shear_y = direction.component(ky) / dz;
+    let shear_z = 1.0 / dz;
+
+    let a = corners[0] - position;
+    let b = corners[1] - position;
+    let c = corners[2] - position;
+    let ax = a.component(kx) - shear_x * a.component(kz);
+    let ay = a.component(ky) - shear_y * a.component(kz);
+    let bx = b.component(kx) - shear_x * b.component(kz);
+    let by = b.component(ky) - shear_y * b.component(kz);
+    let cx = c.component(kx) - shear_x * c.component(kz);
+    let cy = c.component(ky) - shear_y * c.component(kz);
+
+    // Each weight is the edge function of the opposite edge, which runs c to b, a to c and b to a
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+    // Triangles facing away wind the other way round, so their edges are flipped to keep the inside
+    // on the same side
+    let side = det.signum();
+    let edges = [(u, bx - cx, by - cy), (v, cx - ax, cy - ay), (w, ax - bx, ay - by)];
+    for &(weight, dx, dy) in edges.iter() {
+        if weight * side < 0.0 || (weight == 0.0 && !is_top_left(dx * side, dy * side)) {
+            return None;
+        }
+    }
+
+    let t = (u * a.component(kz) + v * b.component(kz) + w * c.component(kz)) * shear_z / det;
+    if t <= 0.0 {
+        return None;
+    }
+    Some((t, [u / det, v / det, w / det]))
+}
+
+// Of an edge and the same edge run backwards, exactly one is a top or left edge
+fn is_top_left(dx: f64, dy: f64) -> bool {
+    dy < 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+// Part of a triangle a point lies on, with the corners numbered in the order they were given
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TriangleFeature {
+    Vertex(usize),
+    Edge(usize, usize),
+    Face
+}
+
+// Closest point to p on the triangle, by finding which vertex, edge or face region p projects into
+pub fn closest_point_on_triangle(p: Vector, a: Vector, b: Vector, c: Vector) -> (Vector, TriangleFeature) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let d1 = ab.dot(ap);
+    let d2 = ac.dot(ap);
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (a, TriangleFeature::Vertex(0));
+    }
+
+    let bp = p - b;
+    let d3 = ab.dot(bp);
+    let d4 = ac.dot(bp);
+    if d3 >= 0.0 && d4 <= d3 {
+        return (b, TriangleFeature::Vertex(1));
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return (a + ab * (d1 / (d1 - d3)), TriangleFeature::Edge(0, 1));
+    }
+
+    let cp = p - c;
+    let d5 = ab.dot(cp);
+    let d6 = ac.dot(cp);
+    if d6 >= 0.0 && d5 <= d6 {
+        return (c, TriangleFeature::Vertex(2));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return (a + ac * (d2 / (d2 - d6)), TriangleFeature::Edge(0, 2));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return (b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6))), TriangleFeature::Edge(1, 2));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    (a + ab * (vb * denom) + ac * (vc * denom), TriangleFeature::Face)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(triangles: &[[Vector; 3]], position: Vector, direction: Vector) -> usize {
+        triangles.iter().filter(|&&corners| triangle_intersection(corners, position, direction).is_some()).count()
+    }
+
+    #[test]
+    fn shared_edges_are_hit_once() {
+        // A square split along its diagonal, wound the same way
+        let (a, b, c, d) = (Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0));
+        let triangles = [[a, b, c], [a, c, d]];
+        let down = Vector::new(0.0, 0.0, -1.0);
+        assert_eq!(hits(&triangles, Vector::new(0.5, 0.5, 1.0), down), 1);
+        assert_eq!(hits(&triangles, Vector::new(0.25, 0.25, -1.0), -down), 1);
+        assert_eq!(hits(&triangles, Vector::new(0.0, 0.0, 1.0), Vector::new(0.5, 0.5, -1.0).normalize()), 1);
+    }
+
+    #[test]
+    fn shared_vertices_are_hit_once() {
+        // A square split into a fan around its center
+        let center = Vector::new(0.5, 0.5, 0.0);
+        let corners = [Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0)];
+        let triangles: Vec<[Vector; 3]> = (0..4).map(|i| [center, corners[i], corners[(i + 1) % 4]]).collect();
+        assert_eq!(hits(&triangles, Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0)), 1);
+        assert_eq!(hits(&triangles, Vector::new(0.5, 0.5, -1.0), Vector::new(0.0, 0.0, 1.0)), 1);
+    }
+
+    #[test]
+    fn folds_are_hit_an_even_number_of_times() {
+        // Two triangles sharing an edge that lie over each other seen along z, like the rim of a shape
+        let (a, b) = (Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+        let triangles = [[a, b, Vector::new(0.5, 1.0, 1.0)], [b, a, Vector::new(0.5, 1.0, -1.0)]];
+        assert_eq!(hits(&triangles, Vector::new(0.5, 0.0, 5.0), Vector::new(0.0, 0.0, -1.0)) % 2, 0);
+        assert_eq!(hits(&triangles, Vector::new(0.5, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)) % 2, 0);
+    }
+}
@@ -0,0 +1,347 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+
+use vector::*;
+use tone_mapping::*;
+
+// Opens the file for writing without truncating it, so an unwritable output is caught before rendering.
+// Files that didn't exist yet are removed again, so a render that fails later leaves nothing behind
+pub fn check_writable(path: &str) -> io::Result<()> {
+    let existed = Path::new(path).exists();
+    OpenOptions::new().write(true).create(true).open(path)?;
+    if !existed {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+pub fn write_ppm(path: &str, width: usize, height: usize, colors: &[Vector], tone_mapper: &ToneMapper) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P6\n{} {}\n255\n", width, height)?;
+    file.write_all(&to_rgb_bytes(width, colors, tone_mapper))?;
+    file.flush()
+}
+
+pub fn write_png(path: &str, width: usize, height: usize, colors: &[Vector], tone_mapper: &ToneMapper) -> io::Result<()> {
+    let rgb = to_rgb_bytes(width, colors, tone_mapper);
+
+    // Every scanline is prefixed with filter type 0 (None)
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for row in rgb.chunks(width * 3) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&be_bytes(width as u32));
+    header.extend_from_slice(&be_bytes(height as u32));
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A])?;
+    write_png_chunk(&mut file, b"IHDR", &header)?;
+    write_png_chunk(&mut file, b"IDAT", &zlib_stored(&raw))?;
+    write_png_chunk(&mut file, b"IEND", &[])?;
+    file.flush()
+}
+
+pub fn write_hdr(path: &str, width: usize, height: usize, colors: &[Vector]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+    for color in colors {
+        file.write_all(&to_rgbe(*color))?;
+    }
+    file.flush()
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ExrPixelType {
+    Half,
+    Float
+}
+
+pub fn write_exr(path: &str, width: usize, height: usize, colors: &[Vector], pixel_type: ExrPixelType) -> io::Result<()> {
+    let (type_id, pixel_size) = match pixel_type {
+        ExrPixelType::Half => (1, 2),
+        ExrPixelType::Float => (2, 4)
+    };
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2F, 0x31, 0x01]);
+    header.extend_from_slice(&le_bytes(2));
+
+    // Channels must be listed in alphabetical order
+    let mut channels = Vec::new();
+    for name in &[b'B', b'G', b'R'] {
+        channels.extend_from_slice(&[*name, 0]);
+        channels.extend_from_slice(&le_bytes(type_id));
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&le_bytes(1));
+        channels.extend_from_slice(&le_bytes(1));
+    }
+    channels.push(0);
+    write_exr_attribute(&mut header, "channels", "chlist", &channels);
+    write_exr_attribute(&mut header, "compression", "compression", &[0]);
+
+    let mut window = Vec::new();
+    window.extend_from_slice(&le_bytes(0));
+    window.extend_from_slice(&le_bytes(0));
+    window.extend_from_slice(&le_bytes(width as u32 - 1));
+    window.extend_from_slice(&le_bytes(height as u32 - 1));
+    write_exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_exr_attribute(&mut header, "pixelAspectRatio", "float", &le_bytes(1.0f32.to_bits()));
+    write_exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_exr_attribute(&mut header, "screenWindowWidth", "float", &le_bytes(1.0f32.to_bits()));
+    header.push(0);
+
+    let line_size = 8 + width * 3 * pixel_size;
+    let first_line = header.len() + height * 8;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header)?;
+    for y in 0..height {
+        let offset = (first_line + y * line_size) as u64;
+        file.write_all(&le_bytes(offset as u32))?;
+        file.write_all(&le_bytes((offset >> 32) as u32))?;
+    }
+
+    for (y, row) in colors.chunks(width).enumerate() {
+        file.write_all(&le_bytes(y as u32))?;
+        file.write_all(&le_bytes((width * 3 * pixel_size) as u32))?;
+        for channel in 0..3 {
+            for color in row {
+                let value = match channel {
+                    0 => color.z,
+                    1 => color.y,
+                    _ => color.x
+                } as f32;
+                match pixel_type {
+                    ExrPixelType::Half => {
+                        let half = f32_to_half(value);
+                        file.write_all(&[half as u8, (half >> 8) as u8])?;
+                    },
+                    ExrPixelType::Float => file.write_all(&le_bytes(value.to_bits()))?
+                }
+            }
+        }
+    }
+    file.flush()
+}
+
+fn write_exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&le_bytes(value.len() as u32));
+    header.extend_from_slice(value);
+}
+
+// Shared exponent encoding from the Radiance file format
+fn to_rgbe(color: Vector) -> [u8; 4] {
+    let r = color.x.max(0.0);
+    let g = color.y.max(0.0);
+    let b = color.z.max(0.0);
+    let v = r.max(g).max(b);
+    if !(v >= 1e-32) || !v.is_finite() {
+        return [0, 0, 0, 0];
+    }
+
+    let mut exponent = v.log2().floor() as i32 + 1;
+    if v / 2f64.powi(exponent) >= 1.0 {
+        exponent = exponent + 1;
+    }
+    let scale = 256.0 / 2f64.powi(exponent);
+    [
+        (r * scale).min(255.0) as u8,
+        (g * scale).min(255.0) as u8,
+        (b * scale).min(255.0) as u8,
+        (exponent + 128) as u8
+    ]
+}
+
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x7FFFFF;
+
+    if exponent == 0xFF {
+        // Infinity stays infinity and NaN keeps a non zero mantissa
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        // Subnormal half, shift the mantissa including the implicit leading bit
+        let mantissa = mantissa | 0x800000;
+        let shift = (14 - half_exponent) as u32;
+        let mut half_mantissa = mantissa >> shift;
+        if (mantissa >> (shift - 1)) & 1 != 0 {
+            half_mantissa = half_mantissa + 1;
+        }
+        return sign | half_mantissa as u16;
+    }
+
+    let mut half = ((half_exponent as u32) << 10) | (mantissa >> 13);
+    // Round to nearest, carrying into the exponent if needed
+    if mantissa & 0x1000 != 0 {
+        half = half + 1;
+    }
+    sign | half as u16
+}
+
+fn to_rgb_bytes(width: usize, colors: &[Vector], tone_mapper: &ToneMapper) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(colors.len() * 3);
+    for (i, color) in colors.iter().enumerate() {
+        let int_color = tone_mapper.to_int_color(*color, i % width, i / width);
+        bytes.push((int_color >> 16) as u8);
+        bytes.push((int_color >> 8) as u8);
+        bytes.push(int_color as u8);
+    }
+    bytes
+}
+
+fn write_png_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&be_bytes(data.len() as u32))?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(crc32_update(0xFFFFFFFF, kind), data);
+    out.write_all(&be_bytes(crc))
+}
+
+// Wraps the data in uncompressed deflate blocks so no compression library is needed
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = data.chunks(65535).collect();
+    if blocks.is_empty() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    for (i, block) in blocks.iter().enumerate() {
+        let last = if i == blocks.len() - 1 { 1 } else { 0 };
+        let len = block.len() as u16;
+        out.push(last);
+        out.push(len as u8);
+        out.push((len >> 8) as u8);
+        out.push(!len as u8);
+        out.push((!len >> 8) as u8);
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&be_bytes(adler32(data)));
+    out
+}
+
+fn be_bytes(value: u32) -> [u8; 4] {
+    [(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]
+}
+
+fn le_bytes(value: u32) -> [u8; 4] {
+    [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]
+}
+
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    crc32_update(crc, data) ^ 0xFFFFFFFF
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB88320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::*;
+
+    fn half_to_f64(half: u16) -> f64 {
+        let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+        let exponent = ((half >> 10) & 0x1F) as i32;
+        let mantissa = (half & 0x3FF) as f64;
+        match exponent {
+            0 => sign * mantissa * 2f64.powi(-24),
+            0x1F => if mantissa == 0.0 { sign * INFINITY } else { NAN },
+            _ => sign * (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15)
+        }
+    }
+
+    #[test]
+    fn halves_of_special_values() {
+        assert_eq!(f32_to_half(0.0), 0);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3C00);
+        assert_eq!(f32_to_half(-2.0), 0xC000);
+        assert_eq!(f32_to_half(65504.0), 0x7BFF);
+        // Past halfway to the next power of two rounds up to infinity
+        assert_eq!(f32_to_half(65520.0), 0x7C00);
+        assert_eq!(f32_to_half(::std::f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_half(::std::f32::NEG_INFINITY), 0xFC00);
+        let nan = f32_to_half(::std::f32::NAN);
+        assert!(nan & 0x7C00 == 0x7C00 && nan & 0x3FF != 0);
+        // Smallest subnormal, and values too small for one
+        assert_eq!(f32_to_half(2f32.powi(-24)), 1);
+        assert_eq!(f32_to_half(2f32.powi(-26)), 0);
+    }
+
+    #[test]
+    fn halves_round_to_nearest() {
+        // Values spread over the whole half range, including subnormals
+        let mut value = 1.0e-8f32;
+        while value < 60000.0 {
+            for &x in [value, -value].iter() {
+                let half = f32_to_half(x);
+                let error = (half_to_f64(half) - x as f64).abs();
+                for &neighbor in [half.wrapping_sub(1), half + 1].iter() {
+                    if neighbor & 0x7C00 != 0x7C00 && (neighbor & 0x8000) == (half & 0x8000) {
+                        assert!(error <= (half_to_f64(neighbor) - x as f64).abs(), "{} became {:#x}", x, half);
+                    }
+                }
+            }
+            value = value * 1.0137;
+        }
+    }
+
+    #[test]
+    fn rgbe_keeps_the_brightest_channel_to_eight_bits() {
+        assert_eq!(to_rgbe(Vector::zero()), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Vector::new(-1.0, NAN, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Vector::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        let mut value = 1.0e-6;
+        while value < 1.0e6 {
+            let color = Vector::new(value, value * 0.3, value * 0.01);
+            let rgbe = to_rgbe(color);
+            assert!(rgbe[0] >= 128, "{} has mantissa {}", value, rgbe[0]);
+            let decoded = rgbe[0] as f64 * 2f64.powi(rgbe[3] as i32 - 136);
+            assert!(decoded <= value && value - decoded < value / 128.0, "{} decoded as {}", value, decoded);
+            value = value * 1.37;
+        }
+    }
+}
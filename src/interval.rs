@@ -0,0 +1,197 @@
+use std::f64::*;
+use std::cmp::Ordering;
+
+use ray::*;
+
+// Span along a ray, measured as distance from the ray origin, where the ray is inside a field.
+// Spans are clipped to the ray so they never start before zero, and lists of them are kept sorted
+// and disjoint. The surface hits at either end travel along with the span, so CSG operators can
+// return the hit of the field they came from
+#[derive(Copy, Clone)]
+pub struct Interval {
+    pub start: f64,
+    pub end: f64,
+    // None where the span is cut off by the ends of the ray rather than a surface, or where the field
+    // doesn't record them
+    pub enter: Option<Hit>,
+    pub exit: Option<Hit>
+}
+
+impl Interval {
+    pub fn new(start: f64, end: f64) -> Interval {
+        Interval {
+            start: start,
+            end: end,
+            enter: None,
+            exit: None
+        }
+    }
+
+    pub fn with_hits(self, enter: Option<Hit>, exit: Option<Hit>) -> Interval {
+        Interval {
+            enter: enter,
+            exit: exit,
+            ..self
+        }
+    }
+
+    pub fn whole_ray() -> Interval {
+        Interval::new(0.0, INFINITY)
+    }
+}
+
+pub fn union_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    let mut sorted = Vec::with_capacity(a.len() + b.len());
+    sorted.extend_from_slice(a);
+    sorted.extend_from_slice(b);
+    sorted.sort_by(|x, y| x.start.partial_cmp(&y.start).unwrap_or(Ordering::Equal));
+
+    let mut result: Vec<Interval> = Vec::with_capacity(sorted.len());
+    for interval in sorted {
+        if let Some(last) = result.last_mut() {
+            if interval.start <= last.end {
+                if interval.end > last.end {
+                    last.end = interval.end;
+                    last.exit = interval.exit;
+                }
+                continue;
+            }
+        }
+        result.push(interval);
+    }
+    result
+}
+
+pub fn intersect_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    let mut j = 0;
+    while i < a.len() && j < b.len() {
+        // The span starts where the later of the two starts and ends where the earlier one ends
+        let (start, enter) = if b[j].start > a[i].start { (b[j].start, b[j].enter) } else { (a[i].start, a[i].enter) };
+        let (end, exit) = if b[j].end < a[i].end { (b[j].end, b[j].exit) } else { (a[i].end, a[i].exit) };
+        if start < end {
+            result.push(Interval::new(start, end).with_hits(enter, exit));
+        }
+        // Whichever span ends first can't overlap anything further along the other list
+        if a[i].end < b[j].end {
+            i = i + 1;
+        } else {
+            j = j + 1;
+        }
+    }
+    result
+}
+
+pub fn subtract_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    if a.is_empty() || b.is_empty() {
+        return a.to_vec();
+    }
+    intersect_intervals(a, &complement_intervals(b))
+}
+
+// Leaving a field is entering its complement, through the same surface facing the other way
+pub fn complement_intervals(intervals: &[Interval]) -> Vec<Interval> {
+    let mut result = Vec::with_capacity(intervals.len() + 1);
+    let mut start = 0.0;
+    let mut enter = None;
+    for interval in intervals {
+        if interval.start > start {
+            result.push(Interval::new(start, interval.start).with_hits(enter, interval.enter.map(|hit| hit.flipped())));
+        }
+        start = interval.end;
+        enter = interval.exit.map(|hit| hit.flipped());
+    }
+    if start < INFINITY {
+        result.push(Interval::new(start, INFINITY).with_hits(enter, None));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vector::*;
+    use characteristics::*;
+
+    fn spans(intervals: &[Interval]) -> Vec<(f64, f64)> {
+        intervals.iter().map(|interval| (interval.start, interval.end)).collect()
+    }
+
+    fn intervals(spans: &[(f64, f64)]) -> Vec<Interval> {
+        spans.iter().map(|&(start, end)| Interval::new(start, end)).collect()
+    }
+
+    fn hit(t: f64) -> Hit {
+        Hit {
+            t: t,
+            position: Vector::new(0.0, 0.0, t),
+            geometric_normal: Vector::new(0.0, 0.0, -1.0),
+            shading_normal: Vector::new(0.0, 0.0, -1.0),
+            uv: (0.0, 0.0),
+            front_face: true,
+            object_id: 0,
+            material: Characteristics::default()
+        }
+    }
+
+    #[test]
+    fn union_merges_overlapping_spans() {
+        let a = intervals(&[(1.0, 3.0), (5.0, 6.0)]);
+        let b = intervals(&[(2.0, 4.0), (6.0, 7.0), (9.0, INFINITY)]);
+        assert_eq!(spans(&union_intervals(&a, &b)), vec![(1.0, 4.0), (5.0, 7.0), (9.0, INFINITY)]);
+        assert_eq!(spans(&union_intervals(&a, &[])), spans(&a));
+    }
+
+    #[test]
+    fn intersection_keeps_the_overlaps() {
+        let a = intervals(&[(1.0, 4.0), (5.0, 8.0)]);
+        let b = intervals(&[(2.0, 6.0), (7.0, INFINITY)]);
+        assert_eq!(spans(&intersect_intervals(&a, &b)), vec![(2.0, 4.0), (5.0, 6.0), (7.0, 8.0)]);
+        assert!(intersect_intervals(&a, &intervals(&[(4.0, 5.0)])).is_empty());
+    }
+
+    #[test]
+    fn complement_covers_the_rest_of_the_ray() {
+        assert_eq!(spans(&complement_intervals(&intervals(&[(1.0, 2.0), (3.0, 4.0)]))), vec![(0.0, 1.0), (2.0, 3.0), (4.0, INFINITY)]);
+        assert_eq!(spans(&complement_intervals(&[])), vec![(0.0, INFINITY)]);
+        assert!(complement_intervals(&[Interval::whole_ray()]).is_empty());
+    }
+
+    #[test]
+    fn subtraction_removes_the_second_spans() {
+        let a = intervals(&[(0.0, 10.0)]);
+        let b = intervals(&[(2.0, 3.0), (5.0, INFINITY)]);
+        assert_eq!(spans(&subtract_intervals(&a, &b)), vec![(0.0, 2.0), (3.0, 5.0)]);
+        assert_eq!(spans(&subtract_intervals(&a, &[])), spans(&a));
+    }
+
+    #[test]
+    fn hits_follow_the_boundaries() {
+        let a = vec![Interval::new(1.0, 3.0).with_hits(Some(hit(1.0)), Some(hit(3.0)))];
+        let b = vec![Interval::new(2.0, 5.0).with_hits(Some(hit(2.0)), Some(hit(5.0)))];
+
+        let union = union_intervals(&a, &b);
+        assert_eq!(union[0].enter.unwrap().t, 1.0);
+        assert_eq!(union[0].exit.unwrap().t, 5.0);
+
+        let intersection = intersect_intervals(&a, &b);
+        assert_eq!(intersection[0].enter.unwrap().t, 2.0);
+        assert_eq!(intersection[0].exit.unwrap().t, 3.0);
+
+        // Leaving a through the carved surface of b, which faces the other way
+        let difference = subtract_intervals(&a, &b);
+        assert_eq!(difference[0].enter.unwrap().t, 1.0);
+        let exit = difference[0].exit.unwrap();
+        assert_eq!(exit.t, 2.0);
+        assert_eq!(exit.geometric_normal, Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(exit.shading_normal, Vector::new(0.0, 0.0, 1.0));
+        assert!(!exit.front_face);
+
+        // The complement starts at the origin, where there is no surface
+        let complement = complement_intervals(&a);
+        assert!(complement[0].enter.is_none());
+        assert_eq!(complement[1].enter.unwrap().t, 3.0);
+        assert!(complement[1].exit.is_none());
+    }
+}
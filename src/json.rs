@@ -0,0 +1,370 @@
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum JsonKind {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>)
+}
+
+#[derive(Clone, Debug)]
+pub struct JsonValue {
+    pub kind: JsonKind,
+    pub line: usize,
+    pub column: usize
+}
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub line: usize,
+    pub column: usize
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl JsonValue {
+    pub fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            message: message,
+            line: self.line,
+            column: self.column
+        })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.kind {
+            JsonKind::Null => "null",
+            JsonKind::Bool(_) => "a boolean",
+            JsonKind::Number(_) => "a number",
+            JsonKind::String(_) => "a string",
+            JsonKind::Array(_) => "an array",
+            JsonKind::Object(_) => "an object"
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self.kind {
+            JsonKind::Object(ref members) => members.iter().find(|member| member.0 == key).map(|member| &member.1),
+            _ => None
+        }
+    }
+
+    pub fn as_number(&self) -> Result<f64, ParseError> {
+        match self.kind {
+            JsonKind::Number(number) => Ok(number),
+            _ => self.error(format!("Expected a number but found {}", self.type_name()))
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, ParseError> {
+        match self.kind {
+            JsonKind::Bool(value) => Ok(value),
+            _ => self.error(format!("Expected a boolean but found {}", self.type_name()))
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, ParseError> {
+        match self.kind {
+            JsonKind::String(ref string) => Ok(string),
+            _ => self.error(format!("Expected a string but found {}", self.type_name()))
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&Vec<JsonValue>, ParseError> {
+        match self.kind {
+            JsonKind::Array(ref values) => Ok(values),
+            _ => self.error(format!("Expected an array but found {}", self.type_name()))
+        }
+    }
+
+    pub fn as_object(&self) -> Result<&Vec<(String, JsonValue)>, ParseError> {
+        match self.kind {
+            JsonKind::Object(ref members) => Ok(members),
+            _ => self.error(format!("Expected an object but found {}", self.type_name()))
+        }
+    }
+}
+
+pub fn parse_json(text: &str) -> Result<JsonValue, ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        index: 0,
+        line: 1,
+        column: 1
+    };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.index < parser.chars.len() {
+        return parser.error("Unexpected trailing characters".to_string());
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize
+}
+
+impl Parser {
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            message: message,
+            line: self.line,
+            column: self.column
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).cloned()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let next = self.peek();
+        if let Some(c) = next {
+            self.index = self.index + 1;
+            if c == '\n' {
+                self.line = self.line + 1;
+                self.column = 1;
+            } else {
+                self.column = self.column + 1;
+            }
+        }
+        next
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.advance();
+                Ok(())
+            },
+            Some(c) => self.error(format!("Expected '{}' but found '{}'", expected, c)),
+            None => self.error(format!("Expected '{}' but reached the end of the file", expected))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, ParseError> {
+        let line = self.line;
+        let column = self.column;
+        let kind = match self.peek() {
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('"') => JsonKind::String(self.parse_string()?),
+            Some(c) if c == '-' || c.is_digit(10) => JsonKind::Number(self.parse_number()?),
+            Some(c) if c.is_alphabetic() => self.parse_literal()?,
+            Some(c) => return self.error(format!("Unexpected character '{}'", c)),
+            None => return self.error("Unexpected end of file".to_string())
+        };
+        Ok(JsonValue {
+            kind: kind,
+            line: line,
+            column: column
+        })
+    }
+
+    fn parse_object(&mut self) -> Result<JsonKind, ParseError> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(JsonKind::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return self.error("Expected a quoted key".to_string());
+            }
+            let key = self.parse_string()?;
+            if members.iter().any(|member: &(String, JsonValue)| member.0 == key) {
+                return self.error(format!("Duplicate key \"{}\"", key));
+            }
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some('}') => return Ok(JsonKind::Object(members)),
+                _ => return self.error("Expected ',' or '}' after object member".to_string())
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonKind, ParseError> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(JsonKind::Array(values));
+        }
+        loop {
+            self.skip_whitespace();
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.advance() {
+                Some(',') => continue,
+                Some(']') => return Ok(JsonKind::Array(values)),
+                _ => return self.error("Expected ',' or ']' after array element".to_string())
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.advance() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let mut code = 0;
+                            for _ in 0..4 {
+                                match self.advance().and_then(|c| c.to_digit(16)) {
+                                    Some(digit) => code = code * 16 + digit,
+                                    None => return self.error("Invalid unicode escape".to_string())
+                                }
+                            }
+                            match ::std::char::from_u32(code) {
+                                Some(c) => c,
+                                None => return self.error("Invalid unicode escape".to_string())
+                            }
+                        },
+                        _ => return self.error("Invalid escape sequence".to_string())
+                    };
+                    string.push(escaped);
+                },
+                Some('\n') | None => return self.error("Unterminated string".to_string()),
+                Some(c) => string.push(c)
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f64, ParseError> {
+        let line = self.line;
+        let column = self.column;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+                text.push(c);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        // Numbers too large for a double parse as infinity, which nothing downstream expects
+        match text.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(number),
+            Ok(_) => Err(ParseError {
+                message: format!("Number '{}' is out of range", text),
+                line: line,
+                column: column
+            }),
+            Err(_) => Err(ParseError {
+                message: format!("Invalid number '{}'", text),
+                line: line,
+                column: column
+            })
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<JsonKind, ParseError> {
+        let line = self.line;
+        let column = self.column;
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_alphanumeric() {
+                word.push(c);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        match word.as_str() {
+            "true" => Ok(JsonKind::Bool(true)),
+            "false" => Ok(JsonKind::Bool(false)),
+            "null" => Ok(JsonKind::Null),
+            _ => Err(ParseError {
+                message: format!("Unexpected identifier '{}'", word),
+                line: line,
+                column: column
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> (String, usize, usize) {
+        let error = parse_json(text).unwrap_err();
+        (error.message, error.line, error.column)
+    }
+
+    #[test]
+    fn parses_nested_values() {
+        let value = parse_json("{\"a\": [1, -2.5e1, true, null],\n \"b\": {\"c\": \"x\\\"\\u0041\\n\"}}").unwrap();
+        let a = value.get("a").unwrap().as_array().unwrap();
+        assert_eq!(a[0].as_number().unwrap(), 1.0);
+        assert_eq!(a[1].as_number().unwrap(), -25.0);
+        assert_eq!(a[2].as_bool().unwrap(), true);
+        assert!(match a[3].kind { JsonKind::Null => true, _ => false });
+        let b = value.get("b").unwrap();
+        assert_eq!(b.get("c").unwrap().as_str().unwrap(), "x\"A\n");
+        assert_eq!((b.line, b.column), (2, 7));
+        assert!(value.get("d").is_none());
+    }
+
+    #[test]
+    fn reports_where_errors_are() {
+        assert_eq!(error("[1, 2] x"), ("Unexpected trailing characters".to_string(), 1, 8));
+        assert_eq!(error("{\"a\": 1, \"a\": 2}"), ("Duplicate key \"a\"".to_string(), 1, 13));
+        assert_eq!(error("[1,\n 1e999]"), ("Number '1e999' is out of range".to_string(), 2, 2));
+        assert_eq!(error("[1.2.3]"), ("Invalid number '1.2.3'".to_string(), 1, 2));
+        assert_eq!(error("[nul]"), ("Unexpected identifier 'nul'".to_string(), 1, 2));
+        assert_eq!(error("\"abc\ndef\""), ("Unterminated string".to_string(), 2, 1));
+        assert_eq!(error("[1, 2"), ("Expected ',' or ']' after array element".to_string(), 1, 6));
+    }
+
+    #[test]
+    fn type_errors_name_the_found_type() {
+        let value = parse_json("{\"a\": \"text\"}").unwrap();
+        let error = value.get("a").unwrap().as_number().unwrap_err();
+        assert_eq!((error.line, error.column), (1, 7));
+        assert!(error.message.contains(value.get("a").unwrap().type_name()));
+    }
+}
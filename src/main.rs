@@ -6,6 +6,8 @@ extern crate rand;
 use std::sync::{Arc, Mutex, Barrier};
 use minifb::{Key, WindowOptions, Window, Scale};
 use std::thread;
+use rand::Rng;
+use std::time::{Duration, Instant};
 use std::f64::*;
 
 mod vector;
@@ -16,44 +18,164 @@ mod characteristics;
 mod sky_renderer;
 mod scene_renderer;
 mod geometry;
+mod image_writer;
+mod tone_mapping;
+mod options;
+mod json;
+mod scene_file;
+mod camera;
+mod render_status;
+mod sampler;
+mod tile_scheduler;
+mod ray;
+mod ray_marcher;
+mod primitives;
+mod smooth_csg;
+mod interval;
+mod transform;
+mod domain;
+mod fractals;
+mod noise;
+mod displace;
+mod mesh;
+mod obj;
+mod aabb;
+mod bvh;
 
 use vector::*;
-
-const WIDTH: usize = 960;
-const HEIGHT: usize = 540;
-const THREADS: usize = 4;
+use image_writer::*;
+use tone_mapping::*;
+use options::*;
+use sky_renderer::*;
+use scene_renderer::*;
+use scene_file::*;
+use render_status::*;
 
 fn main() {
-    let mut buffer = vec![0; WIDTH * HEIGHT];
-    let colors_mutex = Arc::new(Mutex::new(vec![Vector::zero(); WIDTH * HEIGHT]));
+    let mut options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            std::process::exit(1);
+        }
+    };
+    if options.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    // Always render from a known seed so any render can be reproduced
+    if options.seed.is_none() {
+        options.seed = Some(rand::thread_rng().gen());
+    }
+
+    // Finding out the output can't be written only after the render would waste the whole render
+    if options.headless {
+        for extension in &OUTPUT_EXTENSIONS {
+            let path = output_path(&options, extension);
+            if let Err(error) = check_writable(&path) {
+                eprintln!("error: {}", write_error(&path, error));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let colors_mutex = Arc::new(Mutex::new(vec![Vector::zero(); options.width * options.height]));
+    let tone_mapper = ToneMapper::new(options.tone_map_operator);
+    let status = Arc::new(RenderStatus::new());
+
+    match options.renderer {
+        Renderer::Sky => sky_renderer(colors_mutex.clone(), status.clone(), &options),
+        Renderer::Scene => {
+            let description = match options.scene_path {
+                Some(ref path) => match load_scene_file(path) {
+                    Ok(description) => description,
+                    Err(message) => {
+                        eprintln!("error: {}", message);
+                        std::process::exit(1);
+                    }
+                },
+                None => default_scene()
+            };
+            scene_renderer(colors_mutex.clone(), status.clone(), description, &options);
+        }
+    }
+
+    if options.headless {
+        if let Err(message) = render_headless(colors_mutex, status, &tone_mapper, &options) {
+            eprintln!("error: {}", message);
+            std::process::exit(1);
+        }
+    } else {
+        render_window(colors_mutex, &tone_mapper, &options);
+    }
+}
 
+fn render_window(colors_mutex: Arc<Mutex<Vec<Vector>>>, tone_mapper: &ToneMapper, options: &Options) {
+    let width = options.width;
+    let height = options.height;
+    let mut buffer = vec![0; width * height];
     let mut window = Window::new("Test - ESC to exit",
-                                WIDTH,
-                                HEIGHT,
+                                width,
+                                height,
                                 WindowOptions {
                                     scale: Scale::X1,
                                     ..WindowOptions::default()
                                 }).unwrap();
 
-    use sky_renderer::*;
-    sky_renderer(colors_mutex.clone(), WIDTH, HEIGHT, THREADS);
-
-    // use scene_renderer::*;
-    // scene_renderer(colors_mutex.clone(), WIDTH, HEIGHT, THREADS);
-
-    let frame_length = std::time::Duration::from_millis(16);
+    let frame_length = Duration::from_millis(16);
     while window.is_open() && !window.is_key_down(Key::Escape) {
         thread::sleep(frame_length);
         let colors_copy: Vec<Vector>;
         {
             colors_copy = colors_mutex.lock().unwrap().to_vec();
         }
-        for x in 0..WIDTH {
-            for y in 0..HEIGHT {
-                let i = x + WIDTH * y;
-                buffer[i] = colors_copy[i].to_int_color();
+        for x in 0..width {
+            for y in 0..height {
+                let i = x + width * y;
+                buffer[i] = tone_mapper.to_int_color(colors_copy[i], x, y);
             }
         }
         window.update_with_buffer(&buffer).unwrap();
     }
 }
+
+const OUTPUT_EXTENSIONS: [&'static str; 4] = ["png", "ppm", "hdr", "exr"];
+
+fn output_path(options: &Options, extension: &str) -> String {
+    format!("{}.{}", options.output_path, extension)
+}
+
+fn write_error(path: &str, error: std::io::Error) -> String {
+    format!("Could not write {}: {}", path, error)
+}
+
+fn render_headless(colors_mutex: Arc<Mutex<Vec<Vector>>>, status: Arc<RenderStatus>, tone_mapper: &ToneMapper, options: &Options) -> Result<(), String> {
+    let width = options.width;
+    let height = options.height;
+    let start = Instant::now();
+    status.wait_until_finished();
+
+    let colors_copy: Vec<Vector>;
+    {
+        colors_copy = colors_mutex.lock().unwrap().to_vec();
+    }
+
+    let samples = status.samples();
+    println!("Rendered {} samples ({:.2} per pixel) in {}s with seed {}",
+             samples,
+             samples as f64 / (width * height) as f64,
+             start.elapsed().as_secs(),
+             options.seed.unwrap());
+
+    let png_path = output_path(options, "png");
+    let ppm_path = output_path(options, "ppm");
+    let hdr_path = output_path(options, "hdr");
+    let exr_path = output_path(options, "exr");
+    write_png(&png_path, width, height, &colors_copy, tone_mapper).map_err(|error| write_error(&png_path, error))?;
+    write_ppm(&ppm_path, width, height, &colors_copy, tone_mapper).map_err(|error| write_error(&ppm_path, error))?;
+    write_hdr(&hdr_path, width, height, &colors_copy).map_err(|error| write_error(&hdr_path, error))?;
+    write_exr(&exr_path, width, height, &colors_copy, options.exr_pixel_type).map_err(|error| write_error(&exr_path, error))?;
+    println!("Wrote {}, {}, {} and {}", png_path, ppm_path, hdr_path, exr_path);
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use geometry::*;
+use interval::*;
+use ray::*;
+use aabb::*;
+use bvh::*;
+use std::f64::*;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// Corners index into the mesh's vertex lists. Triangles without normals are shaded flat, and without
+// UVs they report their barycentric coordinates instead
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize
+}
+
+// Triangles wound counterclockwise seen from outside. Distances, intervals and which side a point
+// is on only make sense for closed meshes whose triangles share their vertices
+pub struct TriangleMesh {
+    pub positions: Vec<Vector>,
+    pub normals: Vec<Vector>,
+    pub uvs: Vec<(f64, f64)>,
+    pub triangles: Vec<Triangle>,
+    pub materials: Vec<Characteristics>,
+    // Angle weighted pseudo-normals of each vertex and edge, from Baerentzen and Aanaes. A point is
+    // inside when it lies behind the pseudo-normal of the part of the surface closest to it. Edges
+    // are keyed by their vertices, smallest first
+    vertex_normals: Vec<Vector>,
+    edge_normals: HashMap<(usize, usize), Vector>,
+    bvh: Bvh
+}
+
+impl TriangleMesh {
+    pub fn new(positions: Vec<Vector>, normals: Vec<Vector>, uvs: Vec<(f64, f64)>, triangles: Vec<Triangle>, materials: Vec<Characteristics>) -> Scene<TriangleMesh> {
+        let bounds: Vec<Aabb> = triangles.iter()
+            .map(|triangle| triangle.positions.iter().fold(Aabb::empty(), |bounds, &index| bounds.grow(positions[index])))
+            .collect();
+
+        let mut vertex_normals = vec![Vector::zero(); positions.len()];
+        let mut edge_normals = HashMap::new();
+        for triangle in &triangles {
+            let corners = triangle.positions;
+            let [a, b, c] = [positions[corners[0]], positions[corners[1]], positions[corners[2]]];
+            let face_normal = (b - a).cross(c - a).normalize();
+            for i in 0..3 {
+                let (previous, next) = (corners[(i + 2) % 3], corners[(i + 1) % 3]);
+                let to_previous = (positions[previous] - positions[corners[i]]).normalize();
+                let to_next = (positions[next] - positions[corners[i]]).normalize();
+                let angle = to_previous.dot(to_next).max(-1.0).min(1.0).acos();
+                vertex_normals[corners[i]] = vertex_normals[corners[i]] + face_normal * angle;
+
+                let edge = edge_key(corners[i], next);
+                let normal = *edge_normals.get(&edge).unwrap_or(&Vector::zero());
+                edge_normals.insert(edge, normal + face_normal);
+            }
+        }
+
+        Scene {
+            field: TriangleMesh {
+                positions: positions,
+                normals: normals,
+                uvs: uvs,
+                triangles: triangles,
+                materials: materials,
+                vertex_normals: vertex_normals,
+                edge_normals: edge_normals,
+                bvh: Bvh::new(&bounds)
+            }
+        }
+    }
+
+    fn corners(&self, triangle: &Triangle) -> [Vector; 3] {
+        [
+            self.positions[triangle.positions[0]],
+            self.positions[triangle.positions[1]],
+            self.positions[triangle.positions[2]]
+        ]
+    }
+
+    fn face_normal(&self, triangle: &Triangle) -> Vector {
+        let [a, b, c] = self.corners(triangle);
+        (b - a).cross(c - a).normalize()
+    }
+
+    fn material(&self, triangle: &Triangle) -> Characteristics {
+        self.materials.get(triangle.material).cloned().unwrap_or(Characteristics::default())
+    }
+
+    // Every distance along the ray where it crosses the surface, sorted, with the triangle crossed
+    // and the barycentric weights of the crossing on it
+    fn crossings(&self, ray: &Ray) -> Vec<(f64, usize, [f64; 3])> {
+        let mut crossings = Vec::new();
+        self.bvh.along_ray(ray, |index| {
+            if let Some((t, weights)) = triangle_intersection(self.corners(&self.triangles[index]), ray.origin, ray.direction) {
+                crossings.push((t, index, weights));
+            }
+        });
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        crossings
+    }
+
+    fn hit(&self, ray: &Ray, triangle: &Triangle, t: f64, weights: [f64; 3]) -> Hit {
+        let geometric_normal = self.face_normal(triangle);
+        let shading_normal = match triangle.normals {
+            Some(normals) => {
+                let normal = self.normals[normals[0]] * weights[0] + self.normals[normals[1]] * weights[1] + self.normals[normals[2]] * weights[2];
+                if normal.length_squared() > 0.0 { normal.normalize() } else { geometric_normal }
+            },
+            None => geometric_normal
+        };
+        let uv = match triangle.uvs {
+            Some(uvs) => {
+                let (a, b, c) = (self.uvs[uvs[0]], self.uvs[uvs[1]], self.uvs[uvs[2]]);
+                (a.0 * weights[0] + b.0 * weights[1] + c.0 * weights[2], a.1 * weights[0] + b.1 * weights[1] + c.1 * weights[2])
+            },
+            None => (weights[1], weights[2])
+        };
+        Hit {
+            t: t,
+            position: ray.at(t),
+            geometric_normal: geometric_normal,
+            shading_normal: shading_normal,
+            uv: uv,
+            front_face: ray.direction.dot(geometric_normal) < 0.0,
+            object_id: 0,
+            material: self.material(triangle)
+        }
+    }
+
+    fn pseudo_normal(&self, triangle: &Triangle, feature: TriangleFeature) -> Vector {
+        match feature {
+            TriangleFeature::Vertex(i) => self.vertex_normals[triangle.positions[i]],
+            TriangleFeature::Edge(i, j) => {
+                let edge = edge_key(triangle.positions[i], triangle.positions[j]);
+                *self.edge_normals.get(&edge).unwrap_or(&Vector::zero())
+            },
+            TriangleFeature::Face => self.face_normal(triangle)
+        }
+    }
+
+    fn closest_triangle(&self, pos: Vector) -> Option<(&Triangle, Vector, TriangleFeature)> {
+        let mut closest = None;
+        let mut closest_dist = INFINITY;
+        self.bvh.closest(pos, |index| {
+            let triangle = &self.triangles[index];
+            let [a, b, c] = self.corners(triangle);
+            let (point, feature) = closest_point_on_triangle(pos, a, b, c);
+            let dist = (point - pos).length();
+            if dist < closest_dist {
+                closest = Some((triangle, point, feature));
+                closest_dist = dist;
+            }
+            dist
+        });
+        closest
+    }
+}
+
+impl Field for TriangleMesh {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<(&Triangle, f64, [f64; 3])> = None;
+        self.bvh.ray_cast(ray, INFINITY, |index, closest_t| {
+            let triangle = &self.triangles[index];
+            match triangle_intersection(self.corners(triangle), ray.origin, ray.direction) {
+                Some((t, weights)) if t < closest_t => {
+                    closest = Some((triangle, t, weights));
+                    Some(t)
+                },
+                _ => None
+            }
+        });
+
+        closest.map(|(triangle, t, weights)| self.hit(ray, triangle, t, weights))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let crossings = self.crossings(ray);
+        let mut intervals = Vec::new();
+        // Rays starting inside enter the mesh without crossing it
+        let mut start = if crossings.len() % 2 == 1 { Some((0.0, None)) } else { None };
+        for (t, index, weights) in crossings {
+            let hit = self.hit(ray, &self.triangles[index], t, weights);
+            match start {
+                Some((entry, enter)) => {
+                    intervals.push(Interval::new(entry, t).with_hits(enter, Some(hit)));
+                    start = None;
+                },
+                None => start = Some((t, Some(hit)))
+            }
+        }
+        if let Some((entry, enter)) = start {
+            intervals.push(Interval::new(entry, INFINITY).with_hits(enter, None));
+        }
+        intervals
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        match self.closest_triangle(pos) {
+            Some((triangle, point, feature)) => {
+                let dist = (point - pos).length();
+                if (pos - point).dot(self.pseudo_normal(triangle, feature)) < 0.0 { -dist } else { dist }
+            },
+            None => INFINITY
+        }
+    }
+
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        self.closest_triangle(pos).map_or(Vector::zero(), |(triangle, _, _)| self.face_normal(triangle))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.closest_triangle(pos).map_or(Characteristics::default(), |(triangle, _, _)| self.material(triangle))
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A cube from -1 to 1, with each face split along a diagonal through its center
+    fn cube() -> TriangleMesh {
+        let positions: Vec<Vector> = (0..8)
+            .map(|i| Vector::new((i & 1) as f64 * 2.0 - 1.0, (i >> 1 & 1) as f64 * 2.0 - 1.0, (i >> 2 & 1) as f64 * 2.0 - 1.0))
+            .collect();
+        let mut triangles = Vec::new();
+        for axis in 0..3 {
+            for side in 0..2 {
+                let (p, q) = ((axis + 1) % 3, (axis + 2) % 3);
+                let corner = |u: usize, v: usize| side << axis | u << p | v << q;
+                let quad = [corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)];
+                for &fan in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]].iter() {
+                    let [a, b, c] = [positions[fan[0]], positions[fan[1]], positions[fan[2]]];
+                    let outward = (b - a).cross(c - a).dot(a + b + c) > 0.0;
+                    triangles.push(Triangle {
+                        positions: if outward { fan } else { [fan[0], fan[2], fan[1]] },
+                        normals: None,
+                        uvs: None,
+                        material: 0
+                    });
+                }
+            }
+        }
+        TriangleMesh::new(positions, Vec::new(), Vec::new(), triangles, vec![Characteristics::default()]).field
+    }
+
+    fn box_distance(p: Vector) -> f64 {
+        let q = p.abs() - Vector::one();
+        let outside = Vector::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+        outside + q.x.max(q.y).max(q.z).min(0.0)
+    }
+
+    #[test]
+    fn distances_match_the_box() {
+        let mesh = cube();
+        let mut points = Vec::new();
+        // A grid that lines up with the faces, edges and corners, plus points off it
+        for i in 0..9 {
+            for j in 0..9 {
+                for k in 0..9 {
+                    points.push(Vector::new(i as f64 - 4.0, j as f64 - 4.0, k as f64 - 4.0) * 0.5);
+                    points.push(Vector::new(i as f64 * 0.37 - 1.3, j as f64 * 0.41 - 1.7, k as f64 * 0.29 - 1.1));
+                }
+            }
+        }
+        for &p in &points {
+            let expected = box_distance(p);
+            let dist = mesh.distance(p);
+            assert!((dist - expected).abs() < 1.0e-9, "distance {} instead of {} at {:?}", dist, expected, p);
+        }
+    }
+
+    #[test]
+    fn hits_blend_the_corner_uvs() {
+        let positions = vec![Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)];
+        let uvs = vec![(0.5, 0.5), (1.0, 0.5), (0.5, 1.0)];
+        let triangle = Triangle {
+            positions: [0, 1, 2],
+            normals: None,
+            uvs: Some([0, 1, 2]),
+            material: 0
+        };
+        let mesh = TriangleMesh::new(positions, Vec::new(), uvs, vec![triangle], vec![Characteristics::default()]).field;
+
+        let hit = mesh.ray_cast(&Ray::new(Vector::new(0.25, 0.25, 1.0), Vector::new(0.0, 0.0, -1.0))).unwrap();
+        assert!((hit.uv.0 - 0.625).abs() < 1.0e-12 && (hit.uv.1 - 0.625).abs() < 1.0e-12);
+        assert!(hit.front_face);
+        let hit = mesh.ray_cast(&Ray::new(Vector::new(0.25, 0.25, -1.0), Vector::new(0.0, 0.0, 1.0))).unwrap();
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn rays_through_shared_edges_see_one_span() {
+        let mesh = cube();
+        // Straight through the diagonals of two faces, and along a plane through the edges
+        let rays = [
+            Ray::new(Vector::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+            Ray::new(Vector::new(-5.0, -5.0, 0.0), Vector::new(1.0, 1.0, 0.0).normalize())
+        ];
+        for ray in rays.iter() {
+            let intervals = mesh.intervals(ray);
+            assert_eq!(intervals.len(), 1);
+            assert!(intervals[0].end < INFINITY);
+        }
+    }
+}
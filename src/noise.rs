@@ -0,0 +1,333 @@
+use vector::*;
+
+// Single octave noise functions. All of them are built from a hash of the lattice cell so a seed
+// picks an independent pattern without any tables
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NoiseBasis {
+    // Gradient noise on a cubic lattice, roughly within -1 to 1
+    Perlin,
+    // Gradient noise on a simplex lattice, fewer axis aligned artifacts than Perlin, roughly within -1 to 1
+    Simplex,
+    // Interpolated random lattice values within -1 to 1
+    Value,
+    // Distance to the closest of one random feature point per cell, within 0 and the cell diagonal
+    Worley
+}
+
+// How octaves are summed
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FractalMode {
+    Fbm,
+    // Sharp crests where the basis crosses zero
+    Ridged,
+    // Absolute values, giving creases in the valleys instead
+    Turbulence
+}
+
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn hash(x: i64, y: i64, z: i64, seed: u64) -> u64 {
+    let mut h = mix(seed.wrapping_add(0x9E3779B97F4A7C15));
+    h = mix(h ^ x as u64);
+    h = mix(h ^ y as u64);
+    mix(h ^ z as u64)
+}
+
+// Uniform in 0 to 1
+fn hash_unit(x: i64, y: i64, z: i64, seed: u64) -> f64 {
+    (hash(x, y, z, seed) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// One of the 12 directions to the edges of a cube
+fn gradient(hash: u64, offset: Vector) -> f64 {
+    let (a, b) = match hash % 12 {
+        0 => (offset.x, offset.y),
+        1 => (-offset.x, offset.y),
+        2 => (offset.x, -offset.y),
+        3 => (-offset.x, -offset.y),
+        4 => (offset.x, offset.z),
+        5 => (-offset.x, offset.z),
+        6 => (offset.x, -offset.z),
+        7 => (-offset.x, -offset.z),
+        8 => (offset.y, offset.z),
+        9 => (-offset.y, offset.z),
+        10 => (offset.y, -offset.z),
+        _ => (-offset.y, -offset.z)
+    };
+    a + b
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+// Blends the values at the 8 corners of the cell containing p
+fn interpolate_cell<F: Fn(i64, i64, i64, Vector) -> f64>(p: Vector, corner: F) -> f64 {
+    let cell = Vector::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let f = p - cell;
+    let (x, y, z) = (cell.x as i64, cell.y as i64, cell.z as i64);
+    let (u, v, w) = (fade(f.x), fade(f.y), fade(f.z));
+
+    let at = |dx: i64, dy: i64, dz: i64| corner(x + dx, y + dy, z + dz, f - Vector::new(dx as f64, dy as f64, dz as f64));
+    lerp(
+        lerp(lerp(at(0, 0, 0), at(1, 0, 0), u), lerp(at(0, 1, 0), at(1, 1, 0), u), v),
+        lerp(lerp(at(0, 0, 1), at(1, 0, 1), u), lerp(at(0, 1, 1), at(1, 1, 1), u), v),
+        w)
+}
+
+fn perlin(p: Vector, seed: u64) -> f64 {
+    interpolate_cell(p, |x, y, z, offset| gradient(hash(x, y, z, seed), offset))
+}
+
+fn value(p: Vector, seed: u64) -> f64 {
+    interpolate_cell(p, |x, y, z, _| hash_unit(x, y, z, seed) * 2.0 - 1.0)
+}
+
+fn simplex(p: Vector, seed: u64) -> f64 {
+    const SKEW: f64 = 1.0 / 3.0;
+    const UNSKEW: f64 = 1.0 / 6.0;
+
+    // Find the simplex containing p by skewing the lattice into cubes
+    let s = (p.x + p.y + p.z) * SKEW;
+    let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+    let t = (i + j + k) * UNSKEW;
+    let d0 = p - Vector::new(i - t, j - t, k - t);
+
+    // Walk from the first corner to the opposite one along the largest offsets first
+    let (first, second) = if d0.x >= d0.y {
+        if d0.y >= d0.z {
+            ((1, 0, 0), (1, 1, 0))
+        } else if d0.x >= d0.z {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if d0.y < d0.z {
+        ((0, 0, 1), (0, 1, 1))
+    } else if d0.x < d0.z {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+
+    let corners = [(0, 0, 0), first, second, (1, 1, 1)];
+    let mut total = 0.0;
+    for (index, &(ci, cj, ck)) in corners.iter().enumerate() {
+        let offset = d0 - Vector::new(ci as f64, cj as f64, ck as f64) + Vector::one() * (index as f64 * UNSKEW);
+        let falloff = 0.5 - offset.length_squared();
+        if falloff > 0.0 {
+            let h = hash(i as i64 + ci, j as i64 + cj, k as i64 + ck, seed);
+            total = total + falloff.powi(4) * gradient(h, offset);
+        }
+    }
+    76.0 * total
+}
+
+fn worley(p: Vector, seed: u64) -> f64 {
+    let (x, y, z) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+    let mut closest = ::std::f64::INFINITY;
+    for dx in -1..2 {
+        for dy in -1..2 {
+            for dz in -1..2 {
+                let (cx, cy, cz) = (x + dx, y + dy, z + dz);
+                let feature = Vector::new(
+                    cx as f64 + hash_unit(cx, cy, cz, seed),
+                    cy as f64 + hash_unit(cx, cy, cz, seed ^ 0x5555),
+                    cz as f64 + hash_unit(cx, cy, cz, seed ^ 0xAAAA));
+                closest = closest.min((feature - p).length_squared());
+            }
+        }
+    }
+    closest.sqrt()
+}
+
+impl NoiseBasis {
+    pub fn sample(&self, p: Vector, seed: u64) -> f64 {
+        match *self {
+            NoiseBasis::Perlin => perlin(p, seed),
+            NoiseBasis::Simplex => simplex(p, seed),
+            NoiseBasis::Value => value(p, seed),
+            NoiseBasis::Worley => worley(p, seed)
+        }
+    }
+
+    // Upper bound on how fast the noise changes per unit distance. Value and Worley noise have exact
+    // bounds, for the gradient noises the bound taken from the fade curve and gradient lengths is
+    // several times too loose, so they use the steepest slope found by sampling and local search plus
+    // about 10% margin. Those two are estimates rather than proven bounds, the tests only repeat the
+    // sampling with other points
+    pub fn lipschitz(&self) -> f64 {
+        match *self {
+            // Steepest slope found is about 3.42
+            NoiseBasis::Perlin => 3.8,
+            // Steepest slope found is about 6.89
+            NoiseBasis::Simplex => 7.6,
+            // The quintic fade has a slope of at most 15/8 across a value range of 2 on each axis
+            NoiseBasis::Value => 2.0 * 1.875 * 3.0f64.sqrt(),
+            // Distance to the closest of a set of points changes no faster than the point moves
+            NoiseBasis::Worley => 1.0
+        }
+    }
+
+    // Upper bound on the absolute value of the noise, found the same way as the slopes
+    pub fn max_value(&self) -> f64 {
+        match *self {
+            // Largest value found is about 1.036
+            NoiseBasis::Perlin => 1.1,
+            // Largest value found is about 0.989, right next to a single lattice corner
+            NoiseBasis::Simplex => 1.1,
+            // A blend of lattice values within -1 to 1
+            NoiseBasis::Value => 1.0,
+            // The feature point of the cell holding the point is never further than the cell diagonal
+            NoiseBasis::Worley => 3.0f64.sqrt()
+        }
+    }
+}
+
+// Several octaves of a basis, each at lacunarity times the frequency and gain times the amplitude of
+// the one before. The sum is normalized by the total amplitude
+#[derive(Copy, Clone, Debug)]
+pub struct Noise {
+    pub basis: NoiseBasis,
+    pub mode: FractalMode,
+    pub seed: u64,
+    pub frequency: f64,
+    pub octaves: usize,
+    pub lacunarity: f64,
+    pub gain: f64
+}
+
+impl Noise {
+    pub fn new(basis: NoiseBasis, mode: FractalMode, seed: u64, frequency: f64, octaves: usize) -> Noise {
+        Noise {
+            basis: basis,
+            mode: mode,
+            seed: seed,
+            frequency: frequency,
+            octaves: octaves,
+            lacunarity: 2.0,
+            gain: 0.5
+        }
+    }
+
+    fn total_amplitude(&self) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        for _ in 0..self.octaves {
+            total = total + amplitude;
+            amplitude = amplitude * self.gain;
+        }
+        total
+    }
+
+    pub fn sample(&self, p: Vector) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = self.frequency;
+        for octave in 0..self.octaves {
+            let n = self.basis.sample(p * frequency, self.seed.wrapping_add(octave as u64));
+            let shaped = match self.mode {
+                FractalMode::Fbm => n,
+                FractalMode::Ridged => {
+                    let ridge = 1.0 - n.abs() / self.basis.max_value();
+                    ridge * ridge
+                },
+                FractalMode::Turbulence => n.abs()
+            };
+            total = total + amplitude * shaped;
+            amplitude = amplitude * self.gain;
+            frequency = frequency * self.lacunarity;
+        }
+        total / self.total_amplitude()
+    }
+
+    pub fn lipschitz(&self) -> f64 {
+        // Squaring the ridge doubles its slope at most, relative to the normalized basis
+        let shaping = match self.mode {
+            FractalMode::Ridged => 2.0 / self.basis.max_value(),
+            _ => 1.0
+        };
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = self.frequency;
+        for _ in 0..self.octaves {
+            total = total + amplitude * frequency;
+            amplitude = amplitude * self.gain;
+            frequency = frequency * self.lacunarity;
+        }
+        shaping * self.basis.lipschitz() * total / self.total_amplitude()
+    }
+
+    pub fn max_value(&self) -> f64 {
+        match self.mode {
+            FractalMode::Ridged => 1.0,
+            _ => self.basis.max_value()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASES: [NoiseBasis; 4] = [NoiseBasis::Perlin, NoiseBasis::Simplex, NoiseBasis::Value, NoiseBasis::Worley];
+
+    // Deterministic points spread over many lattice cells, from the same hash the noise uses
+    fn sample_point(index: i64) -> Vector {
+        Vector::new(hash_unit(index, 0, 0, 1), hash_unit(index, 1, 0, 1), hash_unit(index, 2, 0, 1)) * 40.0 - Vector::one() * 20.0
+    }
+
+    fn sample_direction(index: i64) -> Vector {
+        let d = Vector::new(hash_unit(index, 0, 1, 2), hash_unit(index, 1, 1, 2), hash_unit(index, 2, 1, 2)) - Vector::one() * 0.5;
+        if d.length_squared() > 0.0 { d.normalize() } else { Vector::new(1.0, 0.0, 0.0) }
+    }
+
+    #[test]
+    fn values_stay_within_max_value() {
+        for basis in BASES.iter() {
+            for index in 0..200000 {
+                let value = basis.sample(sample_point(index), index as u64 % 13);
+                assert!(value.abs() <= basis.max_value(), "{:?} reached {} at sample {}", basis, value, index);
+            }
+        }
+    }
+
+    #[test]
+    fn slopes_stay_under_lipschitz() {
+        let step = 1.0e-4;
+        for basis in BASES.iter() {
+            for index in 0..200000 {
+                let p = sample_point(index);
+                let seed = index as u64 % 13;
+                let slope = (basis.sample(p + sample_direction(index) * step, seed) - basis.sample(p, seed)).abs() / step;
+                assert!(slope <= basis.lipschitz(), "{:?} slope {} at sample {}", basis, slope, index);
+            }
+        }
+    }
+
+    #[test]
+    fn fractal_noise_stays_within_its_bounds() {
+        let step = 1.0e-4;
+        for basis in BASES.iter() {
+            for mode in [FractalMode::Fbm, FractalMode::Ridged, FractalMode::Turbulence].iter() {
+                let noise = Noise::new(*basis, *mode, 7, 0.7, 4);
+                for index in 0..20000 {
+                    let p = sample_point(index);
+                    let value = noise.sample(p);
+                    let slope = (noise.sample(p + sample_direction(index) * step) - value).abs() / step;
+                    assert!(value.abs() <= noise.max_value(), "{:?} {:?} reached {}", basis, mode, value);
+                    assert!(slope <= noise.lipschitz(), "{:?} {:?} slope {}", basis, mode, slope);
+                }
+            }
+        }
+    }
+}
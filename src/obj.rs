@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use vector::*;
+use scene::*;
+use characteristics::*;
+use mesh::*;
+
+fn read_file(path: &Path) -> Result<String, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|error| format!("{}: {}", path.display(), error))?;
+    Ok(text)
+}
+
+// Splits a line into words with any comment removed
+fn statement(line: &str) -> Vec<&str> {
+    line.split('#').next().unwrap_or("").split_whitespace().collect()
+}
+
+fn parse_numbers(path: &Path, line: usize, words: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if words.len() < min || words.len() > max {
+        return Err(format!("{}:{}: Expected {} to {} numbers but found {}", path.display(), line, min, max, words.len()));
+    }
+    let mut numbers = Vec::with_capacity(words.len());
+    for word in words {
+        match word.parse::<f64>() {
+            Ok(number) if number.is_finite() => numbers.push(number),
+            _ => return Err(format!("{}:{}: Expected a number but found \"{}\"", path.display(), line, word))
+        }
+    }
+    Ok(numbers)
+}
+
+// Reads the materials of an MTL library, each starting from the given base. The diffuse color
+// becomes the color and the specular color the reflectance. Roughness comes from the PBR extension's
+// Pr when present, otherwise it is converted from the specular exponent
+pub fn load_mtl(path: &Path, base: Characteristics) -> Result<Vec<(String, Characteristics)>, String> {
+    let text = read_file(path)?;
+    // Along with each material, whether its roughness was given directly
+    let mut materials: Vec<(String, Characteristics, bool)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let words = statement(line);
+        if words.is_empty() {
+            continue;
+        }
+
+        if words[0] == "newmtl" {
+            if words.len() != 2 {
+                return Err(format!("{}:{}: Expected a material name", path.display(), line_number));
+            }
+            materials.push((words[1].to_string(), base, false));
+            continue;
+        }
+        let &mut (_, ref mut characteristics, ref mut direct_roughness) = match materials.last_mut() {
+            Some(material) => material,
+            // Statements before the first material have nothing to apply to
+            None => continue
+        };
+        match words[0] {
+            "Kd" => {
+                let color = parse_numbers(path, line_number, &words[1..], 3, 3)?;
+                characteristics.color = Vector::new(color[0], color[1], color[2]);
+            },
+            "Ks" => {
+                let specular = parse_numbers(path, line_number, &words[1..], 3, 3)?;
+                characteristics.reflectance = ((specular[0] + specular[1] + specular[2]) / 3.0).max(0.0).min(1.0);
+            },
+            "Ns" => {
+                let exponent = parse_numbers(path, line_number, &words[1..], 1, 1)?[0].max(0.0);
+                if !*direct_roughness {
+                    characteristics.roughness = (2.0 / (exponent + 2.0)).sqrt();
+                }
+            },
+            "Pr" => {
+                characteristics.roughness = parse_numbers(path, line_number, &words[1..], 1, 1)?[0].max(0.0).min(1.0);
+                *direct_roughness = true;
+            },
+            _ => ()
+        }
+    }
+    Ok(materials.into_iter().map(|(name, characteristics, _)| (name, characteristics)).collect())
+}
+
+// OBJ indices start at one, and negative ones count back from the latest element
+fn resolve_index(path: &Path, line: usize, word: &str, count: usize) -> Result<usize, String> {
+    let index = match word.parse::<i64>() {
+        Ok(index) => index,
+        Err(_) => return Err(format!("{}:{}: Expected an index but found \"{}\"", path.display(), line, word))
+    };
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{}:{}: Index {} is out of range", path.display(), line, index));
+    }
+    Ok(resolved as usize)
+}
+
+// Loads a Wavefront OBJ file as a mesh. Polygons are split into fans of triangles, and MTL libraries
+// are looked up next to the OBJ file. Faces before any usemtl get the default material, which is also
+// what the library's materials start from
+pub fn load_obj(path: &str, default_material: Characteristics) -> Result<Scene<TriangleMesh>, String> {
+    let path = Path::new(path);
+    let text = read_file(path)?;
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut triangles = Vec::new();
+    let mut materials = vec![default_material];
+    let mut library: Vec<(String, Characteristics)> = Vec::new();
+    let mut current_material = 0;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let words = statement(line);
+        if words.is_empty() {
+            continue;
+        }
+
+        match words[0] {
+            "v" => {
+                let position = parse_numbers(path, line_number, &words[1..], 3, 4)?;
+                positions.push(Vector::new(position[0], position[1], position[2]));
+            },
+            "vn" => {
+                let normal = parse_numbers(path, line_number, &words[1..], 3, 3)?;
+                normals.push(Vector::new(normal[0], normal[1], normal[2]).normalize());
+            },
+            "vt" => {
+                let uv = parse_numbers(path, line_number, &words[1..], 1, 3)?;
+                uvs.push((uv[0], if uv.len() > 1 { uv[1] } else { 0.0 }));
+            },
+            "f" => {
+                if words.len() < 4 {
+                    return Err(format!("{}:{}: Faces need at least three corners", path.display(), line_number));
+                }
+                let mut corners = Vec::with_capacity(words.len() - 1);
+                for word in &words[1..] {
+                    // Each corner is position, position/uv, position//normal or position/uv/normal
+                    let parts: Vec<&str> = word.split('/').collect();
+                    if parts.len() > 3 {
+                        return Err(format!("{}:{}: Malformed face corner \"{}\"", path.display(), line_number, word));
+                    }
+                    let position = resolve_index(path, line_number, parts[0], positions.len())?;
+                    let uv = match parts.get(1) {
+                        Some(part) if !part.is_empty() => Some(resolve_index(path, line_number, part, uvs.len())?),
+                        _ => None
+                    };
+                    let normal = match parts.get(2) {
+                        Some(part) if !part.is_empty() => Some(resolve_index(path, line_number, part, normals.len())?),
+                        _ => None
+                    };
+                    corners.push((position, uv, normal));
+                }
+
+                for i in 1..corners.len() - 1 {
+                    let fan = [corners[0], corners[i], corners[i + 1]];
+                    let (a, b, c) = (positions[fan[0].0], positions[fan[1].0], positions[fan[2].0]);
+                    // Degenerate triangles have no normal and can't be hit anyway
+                    if (b - a).cross(c - a).length_squared() == 0.0 {
+                        continue;
+                    }
+                    let all_uvs = fan.iter().all(|corner| corner.1.is_some());
+                    let all_normals = fan.iter().all(|corner| corner.2.is_some());
+                    triangles.push(Triangle {
+                        positions: [fan[0].0, fan[1].0, fan[2].0],
+                        normals: if all_normals { Some([fan[0].2.unwrap(), fan[1].2.unwrap(), fan[2].2.unwrap()]) } else { None },
+                        uvs: if all_uvs { Some([fan[0].1.unwrap(), fan[1].1.unwrap(), fan[2].1.unwrap()]) } else { None },
+                        material: current_material
+                    });
+                }
+            },
+            "mtllib" => {
+                let directory = path.parent().unwrap_or(Path::new(""));
+                for name in &words[1..] {
+                    library.extend(load_mtl(&directory.join(name), default_material)?);
+                }
+            },
+            "usemtl" => {
+                if words.len() != 2 {
+                    return Err(format!("{}:{}: Expected a material name", path.display(), line_number));
+                }
+                // Later definitions of a name replace earlier ones
+                current_material = match library.iter().rev().find(|&&(ref name, _)| name == words[1]) {
+                    Some(&(_, characteristics)) => {
+                        materials.push(characteristics);
+                        materials.len() - 1
+                    },
+                    None => return Err(format!("{}:{}: Unknown material \"{}\"", path.display(), line_number, words[1]))
+                };
+            },
+            // Groups, objects, smoothing groups, lines and points don't affect the surface
+            _ => ()
+        }
+    }
+
+    if triangles.is_empty() {
+        return Err(format!("{}: No faces found", path.display()));
+    }
+    Ok(TriangleMesh::new(positions, normals, uvs, triangles, materials))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(word: &str, count: usize) -> Result<usize, String> {
+        resolve_index(Path::new("model.obj"), 7, word, count)
+    }
+
+    #[test]
+    fn indices_count_from_either_end() {
+        assert_eq!(resolve("1", 3), Ok(0));
+        assert_eq!(resolve("3", 3), Ok(2));
+        assert_eq!(resolve("-1", 3), Ok(2));
+        assert_eq!(resolve("-3", 3), Ok(0));
+    }
+
+    #[test]
+    fn bad_indices_are_reported_with_their_line() {
+        assert_eq!(resolve("0", 3), Err("model.obj:7: Index 0 is out of range".to_string()));
+        assert_eq!(resolve("4", 3), Err("model.obj:7: Index 4 is out of range".to_string()));
+        assert_eq!(resolve("-4", 3), Err("model.obj:7: Index -4 is out of range".to_string()));
+        assert_eq!(resolve("1", 0), Err("model.obj:7: Index 1 is out of range".to_string()));
+        assert_eq!(resolve("x", 3), Err("model.obj:7: Expected an index but found \"x\"".to_string()));
+    }
+}
@@ -0,0 +1,195 @@
+use std::str::FromStr;
+
+use vector::*;
+use tone_mapping::*;
+use image_writer::*;
+use tile_scheduler::*;
+
+pub const USAGE: &'static str = "Usage: rusty_ray_tracer [options]
+
+Options:
+    --renderer <scene|sky>    Renderer to run (default: sky)
+    --width <pixels>          Image width (default: 960)
+    --height <pixels>         Image height (default: 540)
+    --threads <count>         Worker thread count (default: 4)
+    --samples <count>         Samples per pixel before the render stops (default: 64), the sky renderer
+                              draws one animation frame per sample, or a single one with a fixed --sun
+    --time <seconds>          Time budget before the render stops (default: 300)
+    --scene <path>            JSON scene description for the scene renderer
+    --error <threshold>       Stop sampling a pixel once its relative standard error drops below the threshold
+    --output <path>           Output path without extension (default: render)
+    --headless                Render without a window and write the output files
+    --tile-size <pixels>      Edge length of the tiles handed to render threads (default: 32)
+    --tile-order <order>      scanline, spiral or hilbert (default: spiral)
+    --seed <number>           Seed for the random number generator (random when unset)
+    --sun <x,y,z>             Sun direction (default: 0,1,0, the sky renderer animates it when unset)
+    --tone-map <operator>     linear, reinhard, extended-reinhard[:white], aces or uncharted2 (default: aces)
+    --exr <half|float>        Pixel type of the OpenEXR output (default: half)
+    --help                    Print this message";
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Renderer {
+    Scene,
+    Sky
+}
+
+pub struct Options {
+    pub renderer: Renderer,
+    pub width: usize,
+    pub height: usize,
+    pub threads: usize,
+    pub samples_per_pixel: usize,
+    pub time_limit: u64,
+    pub error_threshold: Option<f64>,
+    pub scene_path: Option<String>,
+    pub output_path: String,
+    pub headless: bool,
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    pub seed: Option<u64>,
+    pub sun_direction: Option<Vector>,
+    pub tone_map_operator: ToneMapOperator,
+    pub exr_pixel_type: ExrPixelType,
+    pub help: bool
+}
+
+impl Options {
+    pub fn default() -> Options {
+        Options {
+            renderer: Renderer::Sky,
+            width: 960,
+            height: 540,
+            threads: 4,
+            samples_per_pixel: 64,
+            time_limit: 300,
+            error_threshold: None,
+            scene_path: None,
+            output_path: "render".to_string(),
+            headless: false,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            seed: None,
+            sun_direction: None,
+            tone_map_operator: ToneMapOperator::AcesFilmic,
+            exr_pixel_type: ExrPixelType::Half,
+            help: false
+        }
+    }
+
+    pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+        let mut options = Options::default();
+        let mut args = args;
+
+        while let Some(arg) = args.next() {
+            // Accept both "--flag value" and "--flag=value"
+            let (flag, inline_value) = match arg.find('=') {
+                Some(index) if arg.starts_with("--") => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+                _ => (arg.clone(), None)
+            };
+
+            match flag.as_str() {
+                "--headless" => options.headless = true,
+                "--help" | "-h" => options.help = true,
+                "--renderer" | "--width" | "--height" | "--threads" | "--samples" | "--time" | "--error" |
+                "--scene" | "--output" | "--tile-size" | "--tile-order" | "--seed" | "--sun" | "--tone-map" | "--exr" => {
+                    let value = match inline_value {
+                        Some(value) => value,
+                        None => args.next().ok_or(format!("Missing value for {}", flag))?
+                    };
+                    options.apply(&flag, &value)?;
+                },
+                _ => return Err(format!("Unknown argument '{}'", arg))
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn apply(&mut self, flag: &str, value: &str) -> Result<(), String> {
+        match flag {
+            "--renderer" => {
+                self.renderer = match value {
+                    "scene" => Renderer::Scene,
+                    "sky" => Renderer::Sky,
+                    _ => return Err(format!("Unknown renderer '{}', expected scene or sky", value))
+                };
+            },
+            "--width" => self.width = parse_positive(flag, value)?,
+            "--height" => self.height = parse_positive(flag, value)?,
+            "--threads" => self.threads = parse_positive(flag, value)?,
+            "--samples" => self.samples_per_pixel = parse_positive(flag, value)?,
+            "--time" => self.time_limit = parse_positive(flag, value)?,
+            "--error" => self.error_threshold = Some(parse_positive(flag, value)?),
+            "--scene" => self.scene_path = Some(value.to_string()),
+            "--output" => {
+                if value.is_empty() {
+                    return Err("Output path can not be empty".to_string());
+                }
+                self.output_path = value.to_string();
+            },
+            "--tile-size" => self.tile_size = parse_positive(flag, value)?,
+            "--tile-order" => {
+                self.tile_order = match value {
+                    "scanline" => TileOrder::Scanline,
+                    "spiral" => TileOrder::Spiral,
+                    "hilbert" => TileOrder::Hilbert,
+                    _ => return Err(format!("Unknown tile order '{}', expected scanline, spiral or hilbert", value))
+                };
+            },
+            "--seed" => self.seed = Some(parse_number(flag, value)?),
+            "--sun" => self.sun_direction = Some(parse_direction(flag, value)?),
+            "--tone-map" => self.tone_map_operator = parse_tone_map(value)?,
+            "--exr" => {
+                self.exr_pixel_type = match value {
+                    "half" => ExrPixelType::Half,
+                    "float" => ExrPixelType::Float,
+                    _ => return Err(format!("Unknown exr pixel type '{}', expected half or float", value))
+                };
+            },
+            _ => unreachable!()
+        }
+        Ok(())
+    }
+}
+
+fn parse_number<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value '{}' for {}", value, flag))
+}
+
+fn parse_positive<T: FromStr + PartialOrd + Default>(flag: &str, value: &str) -> Result<T, String> {
+    let number: T = parse_number(flag, value)?;
+    if !(number > T::default()) {
+        return Err(format!("{} must be greater than zero", flag));
+    }
+    Ok(number)
+}
+
+fn parse_direction(flag: &str, value: &str) -> Result<Vector, String> {
+    let components: Vec<&str> = value.split(',').collect();
+    if components.len() != 3 {
+        return Err(format!("{} expects three comma separated numbers, got '{}'", flag, value));
+    }
+    let direction = Vector::new(
+        parse_number(flag, components[0].trim())?,
+        parse_number(flag, components[1].trim())?,
+        parse_number(flag, components[2].trim())?);
+    if !(direction.length() > 0.0) || !direction.length().is_finite() {
+        return Err(format!("{} must be a finite non zero direction", flag));
+    }
+    Ok(direction.normalize())
+}
+
+fn parse_tone_map(value: &str) -> Result<ToneMapOperator, String> {
+    match value {
+        "linear" => Ok(ToneMapOperator::Linear),
+        "reinhard" => Ok(ToneMapOperator::Reinhard),
+        "extended-reinhard" => Ok(ToneMapOperator::ExtendedReinhard(4.0)),
+        "aces" => Ok(ToneMapOperator::AcesFilmic),
+        "uncharted2" => Ok(ToneMapOperator::Uncharted2),
+        _ if value.starts_with("extended-reinhard:") => {
+            let white_point = parse_positive("--tone-map", &value["extended-reinhard:".len()..])?;
+            Ok(ToneMapOperator::ExtendedReinhard(white_point))
+        },
+        _ => Err(format!("Unknown tone map operator '{}'", value))
+    }
+}
@@ -0,0 +1,524 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use aabb::*;
+
+fn sign(value: f64) -> f64 {
+    if value < 0.0 { -1.0 } else { 1.0 }
+}
+
+fn signs(v: Vector) -> Vector {
+    Vector::new(sign(v.x), sign(v.y), sign(v.z))
+}
+
+fn length2(x: f64, y: f64) -> f64 {
+    (x * x + y * y).sqrt()
+}
+
+// Normal of a box centered on the origin, pointing along the closest face when inside
+fn box_normal(p: Vector, half_extents: Vector) -> Vector {
+    let q = p.abs() - half_extents;
+    if q.max_component() > 0.0 {
+        return (q.max(Vector::zero()) * signs(p)).normalize();
+    }
+    if q.x > q.y && q.x > q.z {
+        Vector::new(sign(p.x), 0.0, 0.0)
+    } else if q.y > q.z {
+        Vector::new(0.0, sign(p.y), 0.0)
+    } else {
+        Vector::new(0.0, 0.0, sign(p.z))
+    }
+}
+
+fn box_distance(p: Vector, half_extents: Vector) -> f64 {
+    let q = p.abs() - half_extents;
+    q.max(Vector::zero()).length() + q.max_component().min(0.0)
+}
+
+pub struct AxisAlignedBox {
+    pub center: Vector,
+    pub half_extents: Vector,
+    pub characteristics: Characteristics
+}
+
+impl AxisAlignedBox {
+    pub fn new(center: Vector, half_extents: Vector, chars: Characteristics) -> Scene<AxisAlignedBox> {
+        Scene {
+            field: AxisAlignedBox {
+                center: center,
+                half_extents: half_extents,
+                characteristics: chars
+            }
+        }
+    }
+}
+
+impl Field for AxisAlignedBox {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, self.half_extents)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        box_distance(pos - self.center, self.half_extents)
+    }
+
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        box_normal(pos - self.center, self.half_extents)
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+pub struct RoundedBox {
+    pub center: Vector,
+    pub half_extents: Vector,
+    pub radius: f64,
+    pub characteristics: Characteristics
+}
+
+impl RoundedBox {
+    pub fn new(center: Vector, half_extents: Vector, radius: f64, chars: Characteristics) -> Scene<RoundedBox> {
+        Scene {
+            field: RoundedBox {
+                center: center,
+                half_extents: half_extents,
+                radius: radius,
+                characteristics: chars
+            }
+        }
+    }
+
+    fn inner_extents(&self) -> Vector {
+        (self.half_extents - Vector::one() * self.radius).max(Vector::zero())
+    }
+}
+
+impl Field for RoundedBox {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, self.inner_extents() + Vector::one() * self.radius)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        box_distance(pos - self.center, self.inner_extents()) - self.radius
+    }
+
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        box_normal(pos - self.center, self.inner_extents())
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+// Lies in the xz plane around the y axis
+pub struct Torus {
+    pub center: Vector,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub characteristics: Characteristics
+}
+
+impl Torus {
+    pub fn new(center: Vector, major_radius: f64, minor_radius: f64, chars: Characteristics) -> Scene<Torus> {
+        Scene {
+            field: Torus {
+                center: center,
+                major_radius: major_radius,
+                minor_radius: minor_radius,
+                characteristics: chars
+            }
+        }
+    }
+}
+
+impl Field for Torus {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, Vector::new(self.major_radius + self.minor_radius, self.minor_radius, self.major_radius + self.minor_radius))
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        let p = pos - self.center;
+        length2(length2(p.x, p.z) - self.major_radius, p.y) - self.minor_radius
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let p = pos - self.center;
+        let ring = Vector::new(p.x, 0.0, p.z);
+        if ring.length_squared() == 0.0 {
+            return numerical_normal(self, pos, epsilon);
+        }
+        (p - ring.normalize() * self.major_radius).normalize()
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+// Capped cylinder along the y axis
+pub struct Cylinder {
+    pub center: Vector,
+    pub radius: f64,
+    pub half_height: f64,
+    pub characteristics: Characteristics
+}
+
+impl Cylinder {
+    pub fn new(center: Vector, radius: f64, half_height: f64, chars: Characteristics) -> Scene<Cylinder> {
+        Scene {
+            field: Cylinder {
+                center: center,
+                radius: radius,
+                half_height: half_height,
+                characteristics: chars
+            }
+        }
+    }
+}
+
+impl Field for Cylinder {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, Vector::new(self.radius, self.half_height, self.radius))
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        let p = pos - self.center;
+        let dx = length2(p.x, p.z) - self.radius;
+        let dy = p.y.abs() - self.half_height;
+        dx.max(dy).min(0.0) + length2(dx.max(0.0), dy.max(0.0))
+    }
+
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        let p = pos - self.center;
+        let radial_length = length2(p.x, p.z);
+        let radial = if radial_length > 0.0 {
+            Vector::new(p.x / radial_length, 0.0, p.z / radial_length)
+        } else {
+            Vector::zero()
+        };
+        let cap = Vector::new(0.0, sign(p.y), 0.0);
+        let dx = radial_length - self.radius;
+        let dy = p.y.abs() - self.half_height;
+
+        if dx > 0.0 && dy > 0.0 {
+            (radial * dx + cap * dy).normalize()
+        } else if dy > dx {
+            cap
+        } else {
+            radial
+        }
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+pub struct Capsule {
+    pub start: Vector,
+    pub end: Vector,
+    pub radius: f64,
+    pub characteristics: Characteristics
+}
+
+impl Capsule {
+    pub fn new(start: Vector, end: Vector, radius: f64, chars: Characteristics) -> Scene<Capsule> {
+        Scene {
+            field: Capsule {
+                start: start,
+                end: end,
+                radius: radius,
+                characteristics: chars
+            }
+        }
+    }
+
+    fn closest_on_segment(&self, pos: Vector) -> Vector {
+        let segment = self.end - self.start;
+        let length_squared = segment.length_squared();
+        if length_squared == 0.0 {
+            return self.start;
+        }
+        let h = ((pos - self.start).dot(segment) / length_squared).max(0.0).min(1.0);
+        self.start + segment * h
+    }
+}
+
+impl Field for Capsule {
+    fn bounds(&self) -> Aabb {
+        Aabb::new(self.start.min(self.end), self.start.max(self.end)).expand(self.radius)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        (pos - self.closest_on_segment(pos)).length() - self.radius
+    }
+
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        (pos - self.closest_on_segment(pos)).normalize()
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+// Capped cone along the y axis, with the bottom radius at -half_height and the top radius at +half_height
+pub struct Cone {
+    pub center: Vector,
+    pub half_height: f64,
+    pub bottom_radius: f64,
+    pub top_radius: f64,
+    pub characteristics: Characteristics
+}
+
+impl Cone {
+    pub fn new(center: Vector, half_height: f64, bottom_radius: f64, top_radius: f64, chars: Characteristics) -> Scene<Cone> {
+        Scene {
+            field: Cone {
+                center: center,
+                half_height: half_height,
+                bottom_radius: bottom_radius,
+                top_radius: top_radius,
+                characteristics: chars
+            }
+        }
+    }
+}
+
+impl Field for Cone {
+    fn bounds(&self) -> Aabb {
+        let radius = self.bottom_radius.max(self.top_radius);
+        Aabb::around(self.center, Vector::new(radius, self.half_height, radius))
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        let p = pos - self.center;
+        let h = self.half_height;
+        let r1 = self.bottom_radius;
+        let r2 = self.top_radius;
+        let qx = length2(p.x, p.z);
+        let qy = p.y;
+
+        // Closest point on the caps and on the slanted side in the radial plane
+        let cap_radius = if qy < 0.0 { r1 } else { r2 };
+        let cax = qx - qx.min(cap_radius);
+        let cay = qy.abs() - h;
+        let k2x = r2 - r1;
+        let k2y = 2.0 * h;
+        let t = (((r2 - qx) * k2x + (h - qy) * k2y) / (k2x * k2x + k2y * k2y)).max(0.0).min(1.0);
+        let cbx = qx - r2 + k2x * t;
+        let cby = qy - h + k2y * t;
+
+        let s = if cbx < 0.0 && cay < 0.0 { -1.0 } else { 1.0 };
+        s * (cax * cax + cay * cay).min(cbx * cbx + cby * cby).sqrt()
+    }
+
+    // The same closest points as the distance, the normal points away from whichever is closer
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        let p = pos - self.center;
+        let h = self.half_height;
+        let r1 = self.bottom_radius;
+        let r2 = self.top_radius;
+        let qx = length2(p.x, p.z);
+        let qy = p.y;
+
+        let cap_radius = if qy < 0.0 { r1 } else { r2 };
+        let cax = qx - qx.min(cap_radius);
+        let cay = qy.abs() - h;
+        let k2x = r2 - r1;
+        let k2y = 2.0 * h;
+        let t = (((r2 - qx) * k2x + (h - qy) * k2y) / (k2x * k2x + k2y * k2y)).max(0.0).min(1.0);
+        let cbx = qx - r2 + k2x * t;
+        let cby = qy - h + k2y * t;
+
+        let s = if cbx < 0.0 && cay < 0.0 { -1.0 } else { 1.0 };
+        let (nx, ny) = if cax * cax + cay * cay < cbx * cbx + cby * cby {
+            (cax, cay * sign(qy))
+        } else {
+            (cbx, cby)
+        };
+        let length = length2(nx, ny);
+        // Right on the surface, where the offset vanishes, the slanted side's normal is used
+        let (nx, ny) = if length > 0.0 { (s * nx / length, s * ny / length) } else { (k2y, -k2x) };
+
+        let radial = if qx > 0.0 { Vector::new(p.x / qx, 0.0, p.z / qx) } else { Vector::zero() };
+        (radial * nx + Vector::new(0.0, ny, 0.0)).normalize()
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+// The distance is a bound rather than exact, which is enough for sphere tracing
+pub struct Ellipsoid {
+    pub center: Vector,
+    pub radii: Vector,
+    pub characteristics: Characteristics
+}
+
+impl Ellipsoid {
+    pub fn new(center: Vector, radii: Vector, chars: Characteristics) -> Scene<Ellipsoid> {
+        Scene {
+            field: Ellipsoid {
+                center: center,
+                radii: radii,
+                characteristics: chars
+            }
+        }
+    }
+}
+
+impl Field for Ellipsoid {
+    fn bounds(&self) -> Aabb {
+        Aabb::around(self.center, self.radii)
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        let p = pos - self.center;
+        let k0 = (p / self.radii).length();
+        let k1 = (p / (self.radii * self.radii)).length();
+        if k1 == 0.0 {
+            return -self.radii.x.min(self.radii.y).min(self.radii.z);
+        }
+        k0 * (k0 - 1.0) / k1
+    }
+
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        let p = pos - self.center;
+        (p / (self.radii * self.radii)).normalize()
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+// Hexagonal prism along the z axis, the radius is measured to the middle of each side
+pub struct HexPrism {
+    pub center: Vector,
+    pub radius: f64,
+    pub half_length: f64,
+    pub characteristics: Characteristics
+}
+
+impl HexPrism {
+    pub fn new(center: Vector, radius: f64, half_length: f64, chars: Characteristics) -> Scene<HexPrism> {
+        Scene {
+            field: HexPrism {
+                center: center,
+                radius: radius,
+                half_length: half_length,
+                characteristics: chars
+            }
+        }
+    }
+}
+
+impl Field for HexPrism {
+    fn bounds(&self) -> Aabb {
+        // The corners are farther out than the middle of the sides by 1 / cos(30 degrees)
+        let corner = self.radius * 2.0 / 3.0f64.sqrt();
+        Aabb::around(self.center, Vector::new(corner, corner, self.half_length))
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        let kx = -0.8660254;
+        let ky = 0.5;
+        let kz = 0.57735;
+        let p = (pos - self.center).abs();
+
+        // Fold into the first sector of the hexagon
+        let fold = 2.0 * (kx * p.x + ky * p.y).min(0.0);
+        let px = p.x - fold * kx;
+        let py = p.y - fold * ky;
+
+        let edge = kz * self.radius;
+        let dx = length2(px - px.max(-edge).min(edge), py - self.radius) * sign(py - self.radius);
+        let dy = p.z - self.half_length;
+        dx.max(dy).min(0.0) + length2(dx.max(0.0), dy.max(0.0))
+    }
+
+    // Combines the hexagon's normal and the caps' like a cylinder, with the hexagon's found in the
+    // folded sector and then reflected and mirrored back to where the point is
+    fn normal(&self, pos: Vector, _: f64) -> Vector {
+        let kx = -0.8660254;
+        let ky = 0.5;
+        let kz = 0.57735;
+        let offset = pos - self.center;
+        let p = offset.abs();
+
+        let folded = kx * p.x + ky * p.y < 0.0;
+        let fold = 2.0 * (kx * p.x + ky * p.y).min(0.0);
+        let px = p.x - fold * kx;
+        let py = p.y - fold * ky;
+
+        let edge = kz * self.radius;
+        let (vx, vy) = (px - px.max(-edge).min(edge), py - self.radius);
+        let length = length2(vx, vy);
+        let (mut nx, mut ny) = if length > 0.0 { (vx / length * sign(vy), vy / length * sign(vy)) } else { (0.0, 1.0) };
+        if folded {
+            let along = 2.0 * (kx * nx + ky * ny);
+            nx = nx - along * kx;
+            ny = ny - along * ky;
+        }
+        let side = Vector::new(nx * sign(offset.x), ny * sign(offset.y), 0.0);
+        let cap = Vector::new(0.0, 0.0, sign(offset.z));
+
+        let dx = length * sign(vy);
+        let dy = p.z - self.half_length;
+        if dx > 0.0 && dy > 0.0 {
+            (side * dx + cap * dy).normalize()
+        } else if dy > dx {
+            cap
+        } else {
+            side
+        }
+    }
+
+    fn characteristics(&self, _: Vector) -> Characteristics {
+        self.characteristics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Compares the closed form normals with central differences, away from the creases where the
+    // distance has no gradient. Those show up as differences that change with the step
+    fn check_normals<T: Field>(field: &T, extent: Vector) {
+        let mut compared = 0;
+        for i in 0..2000 {
+            let a = i as f64;
+            let pos = Vector::new((a * 0.37).sin(), (a * 0.53).cos(), (a * 0.71).sin() * (a * 0.13).cos()) * extent;
+            let coarse = numerical_normal(field, pos, 1.0e-4);
+            let fine = numerical_normal(field, pos, 1.0e-5);
+            if (coarse - fine).length() > 1.0e-3 {
+                continue;
+            }
+            let normal = field.normal(pos, 0.0);
+            assert!((normal - fine).length() < 1.0e-3, "{:?} instead of {:?} at {:?}", normal, fine, pos);
+            compared = compared + 1;
+        }
+        assert!(compared > 1500, "only {} points compared", compared);
+    }
+
+    #[test]
+    fn cone_normals_match_the_distance() {
+        for &(bottom, top) in [(1.0, 0.4), (0.5, 1.2), (1.0, 0.0)].iter() {
+            let cone = Cone::new(Vector::new(0.2, -0.1, 0.3), 1.0, bottom, top, Characteristics::default()).field;
+            check_normals(&cone, Vector::new(2.0, 2.0, 2.0));
+        }
+    }
+
+    #[test]
+    fn hex_prism_normals_match_the_distance() {
+        let prism = HexPrism::new(Vector::new(0.2, -0.1, 0.3), 0.8, 1.0, Characteristics::default()).field;
+        check_normals(&prism, Vector::new(1.5, 1.5, 2.0));
+    }
+}
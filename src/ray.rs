@@ -0,0 +1,106 @@
+use vector::*;
+use characteristics::*;
+use distance_field::*;
+use interval::*;
+
+// Normals are sampled over a step that grows with the path length, about a pixel's worth of detail
+// at typical fields of view, so far surfaces don't pick up noise finer than they can show
+const NORMAL_EPSILON_PER_UNIT: f64 = 1.0e-4;
+const MINIMUM_NORMAL_EPSILON: f64 = 1.0e-5;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vector,
+    // Normalized
+    pub direction: Vector,
+    // Step for sampling normals at the origin, and how much it grows per unit along the ray
+    pub footprint: f64,
+    pub spread: f64
+}
+
+impl Ray {
+    // A ray leaving the camera
+    pub fn new(origin: Vector, direction: Vector) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction,
+            footprint: MINIMUM_NORMAL_EPSILON,
+            spread: NORMAL_EPSILON_PER_UNIT
+        }
+    }
+
+    // The next ray of a path that hit something t along this one, the normal step keeps growing from
+    // where this ray left off
+    pub fn continued(&self, t: f64, origin: Vector, direction: Vector) -> Ray {
+        Ray {
+            origin: origin,
+            direction: direction,
+            footprint: self.normal_epsilon(t),
+            spread: self.spread
+        }
+    }
+
+    pub fn at(&self, t: f64) -> Vector {
+        self.origin + self.direction * t
+    }
+
+    pub fn normal_epsilon(&self, t: f64) -> f64 {
+        self.footprint + self.spread * t
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Hit {
+    // Distance along the ray
+    pub t: f64,
+    pub position: Vector,
+    // Both normals point out of the field, the shading normal may be bent away from the true surface
+    pub geometric_normal: Vector,
+    pub shading_normal: Vector,
+    pub uv: (f64, f64),
+    // Whether the ray arrived from outside the field
+    pub front_face: bool,
+    // Zero unless the field was given an id
+    pub object_id: usize,
+    pub material: Characteristics
+}
+
+impl Hit {
+    // Fills in the hit t along the ray by asking the field for its normal and characteristics there,
+    // for fields that only know where the ray hits. The UV coordinates are left at zero
+    pub fn from_field<T: Field + ?Sized>(field: &T, ray: &Ray, t: f64) -> Hit {
+        let position = ray.at(t);
+        let normal = field.normal(position, ray.normal_epsilon(t));
+        Hit {
+            t: t,
+            position: position,
+            geometric_normal: normal,
+            shading_normal: normal,
+            uv: (0.0, 0.0),
+            front_face: ray.direction.dot(normal) < 0.0,
+            object_id: 0,
+            material: field.characteristics(position)
+        }
+    }
+
+    // Where the ray first enters the intervals, which is the surface hit recorded there by whichever
+    // field the boundary came from. Rays starting inside have no such surface, so the field is asked
+    pub fn first_entry<T: Field + ?Sized>(field: &T, ray: &Ray, intervals: &[Interval]) -> Option<Hit> {
+        intervals.iter()
+            .find(|interval| interval.end > interval.start)
+            .map(|interval| match interval.enter {
+                Some(hit) => hit,
+                None => Hit::from_field(field, ray, interval.start)
+            })
+    }
+
+    // The same surface seen from its other side, as the complement of a field sees it
+    pub fn flipped(&self) -> Hit {
+        Hit {
+            geometric_normal: -self.geometric_normal,
+            shading_normal: -self.shading_normal,
+            front_face: !self.front_face,
+            ..*self
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use interval::*;
+use ray::*;
+use aabb::*;
+use std::f64::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct RayMarcher {
+    // Distance at which the ray counts as touching the surface
+    pub epsilon: f64,
+    pub max_steps: usize,
+    pub max_distance: f64
+}
+
+impl RayMarcher {
+    pub fn default() -> RayMarcher {
+        RayMarcher {
+            epsilon: 1.0e-4,
+            max_steps: 256,
+            max_distance: 1000.0
+        }
+    }
+
+    // Sphere tracing: the distance to the closest surface is always a safe step along the ray.
+    // Returns the distance along the ray to the hit
+    pub fn march<T: Field + ?Sized>(&self, field: &T, ray: &Ray) -> Option<f64> {
+        let mut traveled = 0.0;
+        for _ in 0..self.max_steps {
+            let dist = field.distance(ray.at(traveled));
+            if dist < self.epsilon {
+                return Some(traveled);
+            }
+            traveled = traveled + dist;
+            if traveled > self.max_distance {
+                return None;
+            }
+        }
+        None
+    }
+
+    // Walks the whole ray, sphere tracing towards the surface from either side to find every span inside
+    pub fn intervals<T: Field + ?Sized>(&self, field: &T, ray: &Ray) -> Vec<Interval> {
+        let mut intervals = Vec::new();
+        let mut traveled = 0.0;
+        let mut inside = field.distance(ray.origin) < 0.0;
+        let mut start = 0.0;
+        let mut just_crossed = false;
+        for _ in 0..self.max_steps {
+            let dist = field.distance(ray.at(traveled));
+            // Distance to the surface from the side the ray is currently on
+            let clearance = if inside { -dist } else { dist };
+
+            // Right after stepping over the surface the ray is still within epsilon of it, so only
+            // count it as crossing back if it really is on the other side
+            let threshold = if just_crossed { 0.0 } else { self.epsilon };
+            if clearance < threshold {
+                if inside {
+                    intervals.push(Interval::new(start, traveled));
+                } else {
+                    start = traveled;
+                }
+                inside = !inside;
+                just_crossed = true;
+                traveled = traveled + 2.0 * self.epsilon;
+            } else {
+                just_crossed = false;
+                traveled = traveled + clearance.max(self.epsilon);
+            }
+
+            if traveled > self.max_distance {
+                break;
+            }
+        }
+        if inside {
+            intervals.push(Interval::new(start, INFINITY));
+        }
+        intervals
+    }
+}
+
+// Ray casts the wrapped field by sphere tracing its distance with the given settings
+pub struct Marched<T: Field> {
+    pub field: T,
+    pub marcher: RayMarcher
+}
+
+impl<T: Field> Marched<T> {
+    pub fn new(scene: Scene<T>, marcher: RayMarcher) -> Scene<Marched<T>> {
+        Scene {
+            field: Marched {
+                field: scene.field,
+                marcher: marcher
+            }
+        }
+    }
+}
+
+impl<T: Field> Field for Marched<T> {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        self.marcher.march(&self.field, ray).map(|t| Hit::from_field(&self.field, ray, t))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        self.marcher.intervals(&self.field, ray)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.field.bounds()
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(pos)
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        self.field.normal(pos, epsilon)
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(pos)
+    }
+}
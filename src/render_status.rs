@@ -0,0 +1,47 @@
+use std::sync::{Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Shared between the render threads and whoever is waiting on the image
+pub struct RenderStatus {
+    samples: AtomicUsize,
+    finished: AtomicBool,
+    finished_mutex: Mutex<bool>,
+    finished_condvar: Condvar
+}
+
+impl RenderStatus {
+    pub fn new() -> RenderStatus {
+        RenderStatus {
+            samples: AtomicUsize::new(0),
+            finished: AtomicBool::new(false),
+            finished_mutex: Mutex::new(false),
+            finished_condvar: Condvar::new()
+        }
+    }
+
+    pub fn add_samples(&self, count: usize) {
+        self.samples.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples.load(Ordering::Relaxed)
+    }
+
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::SeqCst);
+        let mut finished = self.finished_mutex.lock().unwrap();
+        *finished = true;
+        self.finished_condvar.notify_all();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    pub fn wait_until_finished(&self) {
+        let mut finished = self.finished_mutex.lock().unwrap();
+        while !*finished {
+            finished = self.finished_condvar.wait(finished).unwrap();
+        }
+    }
+}
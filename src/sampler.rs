@@ -0,0 +1,40 @@
+use rand::*;
+
+// Random stream for a single sample of a single pixel. Streams are derived by hashing the global
+// seed with the pixel and sample index so the result doesn't depend on which thread traces it
+pub struct Sampler {
+    state: u64
+}
+
+impl Sampler {
+    pub fn new(seed: u64, pixel: u64, sample: u64) -> Sampler {
+        let mut state = splitmix64(seed);
+        state = splitmix64(state ^ pixel);
+        state = splitmix64(state ^ sample);
+        Sampler {
+            state: state
+        }
+    }
+}
+
+impl Rng for Sampler {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        mix(self.state)
+    }
+}
+
+fn splitmix64(value: u64) -> u64 {
+    mix(value.wrapping_add(0x9E3779B97F4A7C15))
+}
+
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
@@ -4,41 +4,52 @@ use distance_field::*;
 use atmosphere::*;
 use rand::*;
 use characteristics::*;
+use sampler::*;
+use ray::*;
 
 pub struct Scene<T: Field> {
     pub field: T
 }
 
 const MINIMUM_THRESHOLD: f64 = 0.001;
+// Paths still bouncing after this many hits are absorbed, so rays can't get trapped forever in
+// crevices where the surface is closer than the offset, as in fractals
+const MAX_BOUNCES: usize = 64;
 impl<T: Field> Scene<T> {
-    pub fn trace(&self, position: Vector, direction: Vector, sun_dir: Vector) -> Vector {
+    pub fn trace(&self, position: Vector, direction: Vector, sun_dir: Vector, sampler: &mut Sampler) -> Vector {
         let mut accumulated_color = Vector::one();
-        let mut current_pos = position;
-        let mut current_direction = direction;
-        loop {
-            let pos = self.field.ray_cast(current_pos, current_direction);
-            match pos {
-                Some(pos) => {
-                    let characteristics = self.field.characteristics(pos);
-                    let normal = self.field.normal(pos);
+        let mut ray = Ray::new(position, direction);
+        for _ in 0..MAX_BOUNCES {
+            match self.field.ray_cast(&ray) {
+                Some(hit) => {
+                    let characteristics = hit.material;
+                    let normal = hit.shading_normal;
                     let material_color = Vector::interpolate(characteristics.color, Vector::one(), characteristics.reflectance) * (1.0 - characteristics.absorbance);
-                    let new_pos = pos + normal * MINIMUM_THRESHOLD;
-                    let mut new_dir = normal + Vector::random();
+                    let new_pos = hit.position + hit.geometric_normal * MINIMUM_THRESHOLD;
+                    let mut new_dir = normal + Vector::random(sampler);
 
-                    if thread_rng().gen_range(0.0, 1.0) < characteristics.reflectance {
-                        let reflection_target = current_direction - 2.0 * normal * normal.dot(current_direction);
+                    if sampler.gen_range(0.0, 1.0) < characteristics.reflectance {
+                        let reflection_target = ray.direction - 2.0 * normal * normal.dot(ray.direction);
                         new_dir = Vector::interpolate(reflection_target, new_dir, characteristics.roughness);
                     }
 
                     accumulated_color = accumulated_color * material_color;
-                    current_pos = new_pos;
-                    current_direction = new_dir.normalize();
+                    ray = ray.continued(hit.t, new_pos, new_dir.normalize());
                 }
                 None => {
-                    return accumulated_color * calculate_sky_color(current_direction, sun_dir);
+                    return accumulated_color * calculate_sky_color(ray.direction, sun_dir);
                 }
             }
         }
+        Vector::zero()
+    }
+}
+
+impl<T: Field + Send + Sync + 'static> Scene<T> {
+    pub fn boxed(self) -> Scene<Box<dyn Field + Send + Sync>> {
+        Scene {
+            field: Box::new(self.field)
+        }
     }
 }
 
@@ -46,11 +57,7 @@ impl<T: Field> Not for Scene<T> {
     type Output = Scene<Negate<T>>;
 
     fn not(self) -> Scene<Negate<T>> {
-        Scene {
-            field: Negate {
-                field: self.field
-            }
-        }
+        Negate::new(self)
     }
 }
 
@@ -58,12 +65,15 @@ impl<T1: Field, T2: Field> Add<Scene<T2>> for Scene<T1> {
     type Output = Scene<Union<T1, T2>>;
 
     fn add(self, rhs: Scene<T2>) -> Scene<Union<T1, T2>> {
-        Scene {
-            field: Union {
-                field1: self.field,
-                field2: rhs.field
-            }
-        }
+        Union::new(self, rhs)
+    }
+}
+
+impl<T1: Field, T2: Field> Sub<Scene<T2>> for Scene<T1> {
+    type Output = Scene<Difference<T1, T2>>;
+
+    fn sub(self, rhs: Scene<T2>) -> Scene<Difference<T1, T2>> {
+        Difference::new(self, rhs, false)
     }
 }
 
@@ -71,11 +81,6 @@ impl<T1: Field, T2: Field> Mul<Scene<T2>> for Scene<T1> {
     type Output = Scene<Intersection<T1, T2>>;
 
     fn mul(self, rhs: Scene<T2>) -> Scene<Intersection<T1, T2>> {
-        Scene {
-            field: Intersection {
-                field1: self.field,
-                field2: rhs.field
-            }
-        }
+        Intersection::new(self, rhs)
     }
 }
@@ -0,0 +1,643 @@
+use std::fs::File;
+use std::io::Read;
+
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use json::*;
+use camera::*;
+use ray_marcher::*;
+use primitives::*;
+use smooth_csg::*;
+use transform::*;
+use domain::*;
+use fractals::*;
+use noise::*;
+use displace::*;
+use obj::*;
+
+pub struct SceneDescription {
+    pub scene: Scene<Box<dyn Field + Send + Sync>>,
+    pub camera: Camera,
+    pub sun_direction: Option<Vector>
+}
+
+pub fn load_scene_file(path: &str) -> Result<SceneDescription, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|error| format!("{}: {}", path, error))?;
+    parse_scene(&text).map_err(|error| format!("{}:{}", path, error))
+}
+
+pub fn parse_scene(text: &str) -> Result<SceneDescription, ParseError> {
+    let root = parse_json(text)?;
+    check_keys(&root, &["camera", "sun", "field"])?;
+
+    let camera = match root.get("camera") {
+        Some(camera) => parse_camera(camera)?,
+        None => Camera::look_at(Vector::new(0.0, 0.2, -3.0), Vector::new(0.0, 0.2, -2.0))
+    };
+
+    let sun_direction = match root.get("sun") {
+        Some(sun) => Some(parse_direction(sun)?),
+        None => None
+    };
+
+    Ok(SceneDescription {
+        scene: Scene {
+            field: parse_field(required(&root, "field")?)?
+        },
+        camera: camera,
+        sun_direction: sun_direction
+    })
+}
+
+fn parse_camera(value: &JsonValue) -> Result<Camera, ParseError> {
+    check_keys(value, &["position", "target", "up", "fov", "aspect_ratio", "aperture", "focus_distance", "blades", "blade_rotation"])?;
+    let default = Camera::look_at(Vector::new(0.0, 0.2, -3.0), Vector::new(0.0, 0.2, -2.0));
+
+    let position = match value.get("position") {
+        Some(position) => parse_vector(position)?,
+        None => default.position
+    };
+    let target = match value.get("target") {
+        Some(target) => parse_vector(target)?,
+        None => position + (default.target - default.position)
+    };
+    if position == target {
+        return value.error("Camera position and target must differ".to_string());
+    }
+    let up = match value.get("up") {
+        Some(up) => parse_direction(up)?,
+        None => default.up
+    };
+    let fov = match value.get("fov") {
+        Some(fov) => {
+            let degrees = parse_positive(fov)?;
+            if degrees >= 180.0 {
+                return fov.error(format!("Field of view must be less than 180 degrees but found {}", degrees));
+            }
+            degrees
+        },
+        None => default.fov
+    };
+    let aspect_ratio = match value.get("aspect_ratio") {
+        Some(aspect_ratio) => Some(parse_positive(aspect_ratio)?),
+        None => None
+    };
+
+    let mut camera = Camera::new(position, target, up, fov, aspect_ratio);
+    if let Some(aperture) = value.get("aperture") {
+        camera.aperture = parse_non_negative(aperture)?;
+    }
+    if let Some(focus_distance) = value.get("focus_distance") {
+        camera.focus_distance = parse_positive(focus_distance)?;
+    }
+    if let Some(blades) = value.get("blades") {
+        let count = parse_non_negative(blades)?;
+        if count.fract() != 0.0 || (count > 0.0 && count < 3.0) {
+            return blades.error(format!("Blade count must be zero or a whole number of at least 3 but found {}", count));
+        }
+        camera.blades = count as usize;
+    }
+    if let Some(blade_rotation) = value.get("blade_rotation") {
+        camera.blade_rotation = blade_rotation.as_number()?.to_radians();
+    }
+    Ok(camera)
+}
+
+// Any field can be given an id, which the hits on it report
+fn parse_field(value: &JsonValue) -> Result<Box<dyn Field + Send + Sync>, ParseError> {
+    let field = parse_shape(value)?;
+    let id = parse_count(value, "id", 0)?;
+    if id == 0 {
+        return Ok(field);
+    }
+    Ok(Box::new(Identified::new(Scene { field: field }, id).field))
+}
+
+fn parse_shape(value: &JsonValue) -> Result<Box<dyn Field + Send + Sync>, ParseError> {
+    let field_type = required(value, "type")?;
+    match field_type.as_str()? {
+        "sphere" => {
+            check_field_keys(value, &["type", "position", "radius", "material"])?;
+            let radius = parse_positive(required(value, "radius")?)?;
+            Ok(Box::new(Sphere::new(
+                parse_vector(required(value, "position")?)?,
+                radius,
+                parse_material(value.get("material"))?).field))
+        },
+        "plane" => {
+            check_field_keys(value, &["type", "normal", "point", "material"])?;
+            Ok(Box::new(Plane::new(
+                parse_direction(required(value, "normal")?)?,
+                parse_vector(required(value, "point")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "box" => {
+            check_field_keys(value, &["type", "center", "half_extents", "material"])?;
+            Ok(Box::new(AxisAlignedBox::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive_vector(required(value, "half_extents")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "rounded_box" => {
+            check_field_keys(value, &["type", "center", "half_extents", "radius", "material"])?;
+            Ok(Box::new(RoundedBox::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive_vector(required(value, "half_extents")?)?,
+                parse_positive(required(value, "radius")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "torus" => {
+            check_field_keys(value, &["type", "center", "major_radius", "minor_radius", "material"])?;
+            Ok(Box::new(Torus::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive(required(value, "major_radius")?)?,
+                parse_positive(required(value, "minor_radius")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "cylinder" => {
+            check_field_keys(value, &["type", "center", "radius", "half_height", "material"])?;
+            Ok(Box::new(Cylinder::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive(required(value, "radius")?)?,
+                parse_positive(required(value, "half_height")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "capsule" => {
+            check_field_keys(value, &["type", "start", "end", "radius", "material"])?;
+            Ok(Box::new(Capsule::new(
+                parse_vector(required(value, "start")?)?,
+                parse_vector(required(value, "end")?)?,
+                parse_positive(required(value, "radius")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "cone" => {
+            check_field_keys(value, &["type", "center", "half_height", "bottom_radius", "top_radius", "material"])?;
+            Ok(Box::new(Cone::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive(required(value, "half_height")?)?,
+                parse_non_negative(required(value, "bottom_radius")?)?,
+                parse_non_negative(required(value, "top_radius")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "ellipsoid" => {
+            check_field_keys(value, &["type", "center", "radii", "material"])?;
+            Ok(Box::new(Ellipsoid::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive_vector(required(value, "radii")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "hex_prism" => {
+            check_field_keys(value, &["type", "center", "radius", "half_length", "material"])?;
+            Ok(Box::new(HexPrism::new(
+                parse_vector(required(value, "center")?)?,
+                parse_positive(required(value, "radius")?)?,
+                parse_positive(required(value, "half_length")?)?,
+                parse_material(value.get("material"))?).field))
+        },
+        "mesh" => {
+            // The path is relative to the working directory, like the other paths given to the renderer
+            check_field_keys(value, &["type", "path", "material"])?;
+            let path = required(value, "path")?;
+            match load_obj(path.as_str()?, parse_material(value.get("material"))?) {
+                Ok(mesh) => Ok(Box::new(mesh.field)),
+                Err(error) => path.error(error)
+            }
+        },
+        "mandelbulb" => {
+            check_field_keys(value, &["type", "center", "power", "iterations", "material", "trap_color", "trap"])?;
+            let power = match value.get("power") {
+                Some(power) => {
+                    let number = power.as_number()?;
+                    if !(number >= 2.0) {
+                        return power.error(format!("Expected a power of at least 2 but found {}", number));
+                    }
+                    number
+                },
+                None => 8.0
+            };
+            Ok(Box::new(Mandelbulb::new(
+                parse_vector(required(value, "center")?)?,
+                power,
+                parse_count(value, "iterations", 8)?,
+                parse_trap_coloring(value)?).field))
+        },
+        "mandelbox" => {
+            check_field_keys(value, &["type", "center", "scale", "iterations", "material", "trap_color", "trap"])?;
+            let scale = match value.get("scale") {
+                Some(scale) => {
+                    let number = scale.as_number()?;
+                    if !(number.abs() > 1.0) {
+                        return scale.error(format!("Expected a scale below -1 or above 1 but found {}", number));
+                    }
+                    number
+                },
+                None => -1.5
+            };
+            Ok(Box::new(Mandelbox::new(
+                parse_vector(required(value, "center")?)?,
+                scale,
+                parse_count(value, "iterations", 12)?,
+                parse_trap_coloring(value)?).field))
+        },
+        "menger_sponge" | "sierpinski" => {
+            check_field_keys(value, &["type", "center", "size", "iterations", "material", "trap_color", "trap"])?;
+            let center = parse_vector(required(value, "center")?)?;
+            let size = parse_positive(required(value, "size")?)?;
+            if field_type.as_str()? == "menger_sponge" {
+                Ok(Box::new(MengerSponge::new(center, size, parse_count(value, "iterations", 4)?, parse_trap_coloring(value)?).field))
+            } else {
+                Ok(Box::new(Sierpinski::new(center, size, parse_count(value, "iterations", 8)?, parse_trap_coloring(value)?).field))
+            }
+        },
+        "union" | "intersection" => {
+            check_field_keys(value, &["type", "children"])?;
+            let fields = parse_children(required(value, "children")?)?;
+
+            if field_type.as_str()? == "union" {
+                Ok(Box::new(UnionN::new(fields).field))
+            } else {
+                Ok(Box::new(IntersectionN::new(fields).field))
+            }
+        },
+        "difference" => {
+            check_field_keys(value, &["type", "children", "carve_material"])?;
+            let carve_characteristics = match value.get("carve_material") {
+                Some(carve_material) => carve_material.as_bool()?,
+                None => false
+            };
+
+            // Every later child is carved out of the first one
+            let mut fields = parse_children(required(value, "children")?)?.into_iter();
+            let mut combined = Scene {
+                field: fields.next().unwrap()
+            };
+            for field in fields {
+                let child = Scene {
+                    field: field
+                };
+                combined = Difference::new(combined, child, carve_characteristics).boxed();
+            }
+            Ok(combined.field)
+        },
+        "smooth_union" | "smooth_intersection" | "smooth_difference" => {
+            check_field_keys(value, &["type", "children", "radius", "blend"])?;
+            let radius = parse_positive(required(value, "radius")?)?;
+            let blend = match value.get("blend") {
+                Some(blend) => match blend.as_str()? {
+                    "polynomial" => Blend::Polynomial(radius),
+                    "exponential" => Blend::Exponential(radius),
+                    other => return blend.error(format!("Unknown blend \"{}\", expected polynomial or exponential", other))
+                },
+                None => Blend::Polynomial(radius)
+            };
+
+            // Later children are folded into the first one, so a difference carves all of them out of the first
+            let mut fields = parse_children(required(value, "children")?)?.into_iter();
+            let mut combined = Scene {
+                field: fields.next().unwrap()
+            };
+            for field in fields {
+                let child = Scene {
+                    field: field
+                };
+                combined = match field_type.as_str()? {
+                    "smooth_union" => SmoothUnion::new(combined, child, blend).boxed(),
+                    "smooth_intersection" => SmoothIntersection::new(combined, child, blend).boxed(),
+                    _ => SmoothDifference::new(combined, child, blend).boxed()
+                };
+            }
+            Ok(combined.field)
+        },
+        "negate" => {
+            check_field_keys(value, &["type", "child"])?;
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            Ok(Box::new(Negate::new(child).field))
+        },
+        "transform" => {
+            check_field_keys(value, &["type", "child", "translate", "rotate", "scale"])?;
+            // Scaled first, then rotated, then translated
+            let mut transform = Matrix::identity();
+            if let Some(scale) = value.get("scale") {
+                let factors = match scale.as_number() {
+                    Ok(_) => Vector::one() * parse_positive(scale)?,
+                    Err(_) => parse_positive_vector(scale)?
+                };
+                transform = Matrix::scale(factors) * transform;
+            }
+            if let Some(rotate) = value.get("rotate") {
+                check_keys(rotate, &["axis", "angle"])?;
+                let axis = parse_direction(required(rotate, "axis")?)?;
+                let angle = required(rotate, "angle")?.as_number()?.to_radians();
+                transform = Matrix::rotation(Quaternion::from_axis_angle(axis, angle)) * transform;
+            }
+            if let Some(translate) = value.get("translate") {
+                transform = Matrix::translation(parse_vector(translate)?) * transform;
+            }
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            match Transformed::new(child, transform) {
+                Some(transformed) => Ok(Box::new(transformed.field)),
+                None => value.error("Transform can't be inverted".to_string())
+            }
+        },
+        "repeat" => {
+            check_field_keys(value, &["type", "child", "period", "limit"])?;
+            let period = parse_vector(required(value, "period")?)?;
+            if period.x < 0.0 || period.y < 0.0 || period.z < 0.0 {
+                return value.get("period").unwrap().error("Expected no negative components".to_string());
+            }
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            match value.get("limit") {
+                Some(limit) => {
+                    let limit_vector = parse_vector(limit)?;
+                    for &count in &[limit_vector.x, limit_vector.y, limit_vector.z] {
+                        if !(count >= 0.0) || count.fract() != 0.0 {
+                            return limit.error(format!("Expected whole numbers of copies but found {}", count));
+                        }
+                    }
+                    Ok(Box::new(Repeat::limited(child, period, limit_vector).field))
+                },
+                None => Ok(Box::new(Repeat::new(child, period).field))
+            }
+        },
+        "polar_repeat" => {
+            check_field_keys(value, &["type", "child", "count"])?;
+            let count = required(value, "count")?;
+            let copies = parse_positive(count)?;
+            if copies.fract() != 0.0 {
+                return count.error(format!("Expected a whole number of copies but found {}", copies));
+            }
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            Ok(Box::new(PolarRepeat::new(child, copies as usize).field))
+        },
+        "mirror" => {
+            check_field_keys(value, &["type", "child", "normal", "point"])?;
+            let point = match value.get("point") {
+                Some(point) => parse_vector(point)?,
+                None => Vector::zero()
+            };
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            Ok(Box::new(Mirror::new(child, parse_direction(required(value, "normal")?)?, point).field))
+        },
+        "symmetry" => {
+            check_field_keys(value, &["type", "child", "axes"])?;
+            let axes = required(value, "axes")?;
+            let mut folds = [false; 3];
+            for axis in axes.as_array()? {
+                let index = match axis.as_str()? {
+                    "x" => 0,
+                    "y" => 1,
+                    "z" => 2,
+                    other => return axis.error(format!("Unknown axis \"{}\", expected x, y or z", other))
+                };
+                folds[index] = true;
+            }
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            Ok(Box::new(Symmetry::new(child, folds[0], folds[1], folds[2]).field))
+        },
+        "twist" | "bend" => {
+            // The rate is given in degrees per unit
+            check_field_keys(value, &["type", "child", "rate"])?;
+            let rate = required(value, "rate")?.as_number()?.to_radians();
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            if field_type.as_str()? == "twist" {
+                Ok(Box::new(Twist::new(child, rate).field))
+            } else {
+                Ok(Box::new(Bend::new(child, rate).field))
+            }
+        },
+        "displace" => {
+            check_field_keys(value, &["type", "child", "amplitude", "noise"])?;
+            let amplitude = parse_positive(required(value, "amplitude")?)?;
+            let noise = parse_noise(required(value, "noise")?)?;
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            Ok(Box::new(Displace::new(child, noise, amplitude).field))
+        },
+        "marched" => {
+            check_field_keys(value, &["type", "child", "epsilon", "max_steps", "max_distance"])?;
+            let mut marcher = RayMarcher::default();
+            if let Some(epsilon) = value.get("epsilon") {
+                marcher.epsilon = parse_positive(epsilon)?;
+            }
+            marcher.max_steps = parse_count(value, "max_steps", marcher.max_steps)?;
+            if let Some(max_distance) = value.get("max_distance") {
+                marcher.max_distance = parse_positive(max_distance)?;
+            }
+            let child = Scene {
+                field: parse_field(required(value, "child")?)?
+            };
+            Ok(Box::new(Marched::new(child, marcher).field))
+        },
+        other => field_type.error(format!("Unknown field type \"{}\"", other))
+    }
+}
+
+fn parse_children(value: &JsonValue) -> Result<Vec<Box<dyn Field + Send + Sync>>, ParseError> {
+    let mut fields = Vec::new();
+    for child in value.as_array()? {
+        fields.push(parse_field(child)?);
+    }
+    if fields.is_empty() {
+        return value.error("Expected at least one child".to_string());
+    }
+    Ok(fields)
+}
+
+fn parse_noise(value: &JsonValue) -> Result<Noise, ParseError> {
+    check_keys(value, &["basis", "mode", "seed", "frequency", "octaves", "lacunarity", "gain"])?;
+    let basis = match required(value, "basis")?.as_str()? {
+        "perlin" => NoiseBasis::Perlin,
+        "simplex" => NoiseBasis::Simplex,
+        "value" => NoiseBasis::Value,
+        "worley" => NoiseBasis::Worley,
+        other => return value.get("basis").unwrap().error(format!("Unknown noise basis \"{}\", expected perlin, simplex, value or worley", other))
+    };
+    let mode = match value.get("mode") {
+        Some(mode) => match mode.as_str()? {
+            "fbm" => FractalMode::Fbm,
+            "ridged" => FractalMode::Ridged,
+            "turbulence" => FractalMode::Turbulence,
+            other => return mode.error(format!("Unknown noise mode \"{}\", expected fbm, ridged or turbulence", other))
+        },
+        None => FractalMode::Fbm
+    };
+    let seed = match value.get("seed") {
+        Some(seed) => {
+            let number = parse_non_negative(seed)?;
+            if number.fract() != 0.0 {
+                return seed.error(format!("Expected a whole number seed but found {}", number));
+            }
+            number as u64
+        },
+        None => 0
+    };
+    let frequency = match value.get("frequency") {
+        Some(frequency) => parse_positive(frequency)?,
+        None => 1.0
+    };
+
+    let mut noise = Noise::new(basis, mode, seed, frequency, parse_count(value, "octaves", 1)?);
+    if let Some(lacunarity) = value.get("lacunarity") {
+        noise.lacunarity = parse_positive(lacunarity)?;
+    }
+    if let Some(gain) = value.get("gain") {
+        noise.gain = parse_positive(gain)?;
+    }
+    Ok(noise)
+}
+
+fn parse_count(value: &JsonValue, key: &str, default: usize) -> Result<usize, ParseError> {
+    let member = match value.get(key) {
+        Some(member) => member,
+        None => return Ok(default)
+    };
+    let count = parse_positive(member)?;
+    if count.fract() != 0.0 {
+        return member.error(format!("Expected a whole number but found {}", count));
+    }
+    Ok(count as usize)
+}
+
+// Fractals without a trap color stay the plain material color
+fn parse_trap_coloring(value: &JsonValue) -> Result<TrapColoring, ParseError> {
+    let characteristics = parse_material(value.get("material"))?;
+    let trap_color = match value.get("trap_color") {
+        Some(trap_color) => parse_vector(trap_color)?,
+        None => characteristics.color
+    };
+    let shape = match value.get("trap") {
+        Some(trap) => match trap.as_str()? {
+            "origin" => TrapShape::Origin,
+            "planes" => TrapShape::Planes,
+            other => return trap.error(format!("Unknown orbit trap \"{}\", expected origin or planes", other))
+        },
+        None => TrapShape::Origin
+    };
+    Ok(TrapColoring::new(characteristics, trap_color, shape))
+}
+
+fn parse_material(value: Option<&JsonValue>) -> Result<Characteristics, ParseError> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(Characteristics::matte(Vector::one()))
+    };
+    check_keys(value, &["preset", "color", "roughness", "reflectance", "absorbance"])?;
+
+    let color = match value.get("color") {
+        Some(color) => parse_vector(color)?,
+        None => Vector::one()
+    };
+    let mut characteristics = match value.get("preset") {
+        Some(preset) => match preset.as_str()? {
+            "matte" => Characteristics::matte(color),
+            "mirror" => Characteristics::mirror(color),
+            other => return preset.error(format!("Unknown material preset \"{}\"", other))
+        },
+        None => Characteristics::matte(color)
+    };
+
+    if let Some(roughness) = value.get("roughness") {
+        characteristics.roughness = parse_unit(roughness)?;
+    }
+    if let Some(reflectance) = value.get("reflectance") {
+        characteristics.reflectance = parse_unit(reflectance)?;
+    }
+    if let Some(absorbance) = value.get("absorbance") {
+        characteristics.absorbance = parse_unit(absorbance)?;
+    }
+    Ok(characteristics)
+}
+
+fn required<'a>(value: &'a JsonValue, key: &str) -> Result<&'a JsonValue, ParseError> {
+    value.as_object()?;
+    match value.get(key) {
+        Some(member) => Ok(member),
+        None => value.error(format!("Missing required key \"{}\"", key))
+    }
+}
+
+fn check_keys(value: &JsonValue, allowed: &[&str]) -> Result<(), ParseError> {
+    for &(ref key, ref member) in value.as_object()? {
+        if !allowed.contains(&key.as_str()) {
+            return member.error(format!("Unknown key \"{}\", expected one of: {}", key, allowed.join(", ")));
+        }
+    }
+    Ok(())
+}
+
+fn check_field_keys(value: &JsonValue, allowed: &[&str]) -> Result<(), ParseError> {
+    let mut allowed = allowed.to_vec();
+    allowed.push("id");
+    check_keys(value, &allowed)
+}
+
+fn parse_vector(value: &JsonValue) -> Result<Vector, ParseError> {
+    let components = value.as_array()?;
+    if components.len() != 3 {
+        return value.error(format!("Expected 3 components but found {}", components.len()));
+    }
+    Ok(Vector::new(
+        components[0].as_number()?,
+        components[1].as_number()?,
+        components[2].as_number()?))
+}
+
+fn parse_positive_vector(value: &JsonValue) -> Result<Vector, ParseError> {
+    let vector = parse_vector(value)?;
+    if !(vector.x > 0.0 && vector.y > 0.0 && vector.z > 0.0) {
+        return value.error("Expected all components to be positive".to_string());
+    }
+    Ok(vector)
+}
+
+fn parse_direction(value: &JsonValue) -> Result<Vector, ParseError> {
+    let direction = parse_vector(value)?;
+    if direction.length() == 0.0 {
+        return value.error("Direction must not be zero".to_string());
+    }
+    Ok(direction.normalize())
+}
+
+fn parse_positive(value: &JsonValue) -> Result<f64, ParseError> {
+    let number = value.as_number()?;
+    if !(number > 0.0) {
+        return value.error(format!("Expected a positive number but found {}", number));
+    }
+    Ok(number)
+}
+
+fn parse_non_negative(value: &JsonValue) -> Result<f64, ParseError> {
+    let number = value.as_number()?;
+    if !(number >= 0.0) {
+        return value.error(format!("Expected a non negative number but found {}", number));
+    }
+    Ok(number)
+}
+
+fn parse_unit(value: &JsonValue) -> Result<f64, ParseError> {
+    let number = value.as_number()?;
+    if number < 0.0 || number > 1.0 {
+        return value.error(format!("Expected a number between 0 and 1 but found {}", number));
+    }
+    Ok(number)
+}
@@ -1,106 +1,246 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 use rand::*;
 
 use vector::*;
 use distance_field::*;
 use atmosphere::*;
 use characteristics::*;
-
-const UP: Vector = Vector {
-    x: 0.0,
-    y: 1.0,
-    z: 0.0
-};
-
-pub fn scene_renderer(colors_mutex: Arc<Mutex<Vec<Vector>>>, width: usize, height: usize, threads: usize) {
-    let color_counts_mutex = Arc::new(Mutex::new(vec![0; width * height]));
-    let acc_colors_mutex = Arc::new(Mutex::new(vec![Vector::zero(); width * height]));
-    let forward = (Vector {
-        x: 0.0,
-        y: 0.0,
-        z: 1.0
-    }).normalize();
-
+use options::*;
+use scene_file::*;
+use camera::*;
+use render_status::*;
+use sampler::*;
+use tile_scheduler::*;
+use ray::*;
+
+pub fn default_scene() -> SceneDescription {
     let ground = Plane::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0), Characteristics::matte(Vector::one()));
     let mirror = Sphere::new(Vector::new(-0.8, 0.0, 2.0), 1.0, Characteristics::mirror(Vector::one()));
     let sphere = Sphere::new(Vector::new(2.0, 0.0, -1.2), 1.0, Characteristics::matte(Vector::one()));
     let distant = Sphere::new(Vector::new(4.0, 0.0, 20.0), 1.0, Characteristics::matte(Vector::one()));
 
-    let scene = Arc::new(ground + sphere + mirror + distant);
+    SceneDescription {
+        scene: (ground + sphere + mirror + distant).boxed(),
+        camera: Camera::look_at(Vector::new(0.0, 0.2, -3.0), Vector::new(0.0, 0.2, -2.0)),
+        sun_direction: None
+    }
+}
+
+// Pixels need a few samples before their error estimate can be trusted
+const MIN_CONVERGENCE_SAMPLES: u64 = 16;
 
-    let start_position = Vector {
-        x: 0.0,
-        y: 0.2,
-        z: -3.0
-    };
-    let target = start_position + forward;
-    let right = UP.cross(forward).normalize();
-    let up = forward.cross(right).normalize();
+#[derive(Copy, Clone)]
+struct PixelStats {
+    acc_color: Vector,
+    acc_luminance_squared: f64,
+    count: u64,
+    done: bool
+}
 
-    let scene_width = 1.0;
-    let scene_height = height as f64 / width as f64;
+impl PixelStats {
+    fn relative_error(&self) -> f64 {
+        let count = self.count as f64;
+        let mean = (self.acc_color / count).luminance();
+        let variance = (self.acc_luminance_squared / count - mean * mean).max(0.0);
+        (variance / count).sqrt() / mean.max(1.0e-4)
+    }
+}
 
-    let pixel_width = scene_width / width as f64;
-    let pixel_height = scene_height / height as f64;
+// Samples of one pass over a tile. Each pixel has the position of its first sum and how many samples it
+// took, and the sums after every sample are kept so publishing can drop samples the pixel no longer needs
+struct PassResult {
+    pass: usize,
+    pixel_samples: Vec<(usize, usize)>,
+    running_sums: Vec<(Vector, f64)>
+}
 
-    let half_width = width as f64 / 2.0;
-    let half_height = height as f64 / 2.0;
+struct Accumulation {
+    pixels: Vec<PixelStats>,
+    // Passes of a tile are published strictly in order so the floating point sums don't depend on thread timing
+    tile_passes: Vec<usize>,
+    // Passes that finished before an earlier pass of their tile wait here until it is published
+    pending: Vec<Vec<PassResult>>,
+    done_pixels: usize
+}
 
-    let iterations = 10;
+impl Accumulation {
+    // Adds the pass to the tile's pixels and returns how many of its samples were used
+    fn publish(&mut self, tile: &Tile, width: usize, result: &PassResult, samples_per_pixel: u64, error_threshold: Option<f64>) -> u64 {
+        let mut used_samples = 0;
+        let mut k = 0;
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                let (first_sum, processed_iterations) = result.pixel_samples[k];
+                k = k + 1;
+
+                let stats = &mut self.pixels[x + y * width];
+                // A pixel that finished while this pass was in flight drops the extra samples, and one that
+                // got closer to finishing only takes the samples it still needs
+                if stats.done || processed_iterations == 0 {
+                    continue;
+                }
+                let used = (processed_iterations as u64).min(samples_per_pixel - stats.count);
+                let (acc_color, acc_luminance_squared) = result.running_sums[first_sum + used as usize - 1];
+                stats.acc_color = stats.acc_color + acc_color;
+                stats.acc_luminance_squared = stats.acc_luminance_squared + acc_luminance_squared;
+                stats.count = stats.count + used;
+                used_samples = used_samples + used;
+
+                let converged = match error_threshold {
+                    Some(threshold) => stats.count >= MIN_CONVERGENCE_SAMPLES && stats.relative_error() < threshold,
+                    None => false
+                };
+                if stats.count >= samples_per_pixel || converged {
+                    stats.done = true;
+                    self.done_pixels = self.done_pixels + 1;
+                }
+            }
+        }
+        self.tile_passes[tile.index] = result.pass + 1;
+        used_samples
+    }
+}
 
-    let sun_dir = Vector::new(0.0, 1.0, 0.0).normalize();
+pub fn scene_renderer(colors_mutex: Arc<Mutex<Vec<Vector>>>, status: Arc<RenderStatus>, description: SceneDescription, options: &Options) {
+    let width = options.width;
+    let height = options.height;
+    let pixel_count = width * height;
+    let empty_stats = PixelStats {
+        acc_color: Vector::zero(),
+        acc_luminance_squared: 0.0,
+        count: 0,
+        done: false
+    };
+    let tile_queue = Arc::new(TileQueue::new(width, height, options.tile_size, options.tile_order));
+    let accumulation_mutex = Arc::new(Mutex::new(Accumulation {
+        pixels: vec![empty_stats; pixel_count],
+        tile_passes: vec![0; tile_queue.tiles.len()],
+        pending: (0..tile_queue.tiles.len()).map(|_| Vec::new()).collect(),
+        done_pixels: 0
+    }));
+    let camera = description.camera;
+    let scene = Arc::new(description.scene);
 
-    for _ in 0..threads {
+    let iterations = 10;
+    let seed = options.seed.unwrap_or(0);
+    let samples_per_pixel = options.samples_per_pixel as u64;
+    let error_threshold = options.error_threshold;
+    let start = Instant::now();
+    let time_limit = Duration::from_secs(options.time_limit);
+
+    let sun_dir = options.sun_direction
+        .or(description.sun_direction)
+        .unwrap_or(Vector::new(0.0, 1.0, 0.0))
+        .normalize();
+
+    for _ in 0..options.threads {
         let scene = scene.clone();
-        let color_counts_mutex = color_counts_mutex.clone();
-        let acc_colors_mutex = acc_colors_mutex.clone();
+        let tile_queue = tile_queue.clone();
+        let accumulation_mutex = accumulation_mutex.clone();
         let colors_mutex = colors_mutex.clone();
+        let status = status.clone();
 
         thread::spawn(move || {
-            loop {
-                let mut acc_color = Vector::zero();
-                let mut processed_iterations = 0;
-                let mut rng = thread_rng();
-                let x = rng.gen_range(0, width);
-                let y = rng.gen_range(0, height);
-
-                for i in 0..iterations {
-                    processed_iterations = processed_iterations + 1;
-                    let scene_x = (x as f64 - half_width) / width as f64 + rng.gen_range(0.0, pixel_width);
-                    let scene_y = -(y as f64 - half_height) / width as f64 + rng.gen_range(0.0, pixel_height);
-
-                    let target = target + right * scene_x + up * scene_y;
-                    let dir = (target - start_position).normalize();
-
-                    acc_color = acc_color + scene.trace(
-                        start_position,
-                        dir,
-                        sun_dir
-                    );
-                    match scene.field.ray_cast(start_position, dir) {
-                        Some(intersect) => continue,
-                        None => break
-                    }
-                }
+            let mut remaining_samples = Vec::new();
 
-                let i = x as usize + y as usize * width;
+            while !status.is_finished() {
+                if start.elapsed() >= time_limit {
+                    status.finish();
+                    break;
+                }
 
-                let color_count: u64;
+                let (pass, tile) = tile_queue.claim();
 
+                // Earlier passes of the tile may still be in flight, so these can only be too high
+                remaining_samples.clear();
                 {
-                    let mut acc_colors = acc_colors_mutex.lock().unwrap();
-                    acc_color = acc_colors[i] + acc_color;
-                    acc_colors[i] = acc_color;
-                    let mut color_counts = color_counts_mutex.lock().unwrap();
-                    color_count = color_counts[i] + processed_iterations;
-                    color_counts[i] = color_count;
+                    let accumulation = accumulation_mutex.lock().unwrap();
+                    for y in tile.y..tile.y + tile.height {
+                        for x in tile.x..tile.x + tile.width {
+                            let stats = accumulation.pixels[x + y * width];
+                            remaining_samples.push(if stats.done { 0 } else { samples_per_pixel - stats.count });
+                        }
+                    }
+                }
+
+                let mut pixel_samples = Vec::with_capacity(tile.width * tile.height);
+                let mut running_sums = Vec::new();
+                for y in tile.y..tile.y + tile.height {
+                    for x in tile.x..tile.x + tile.width {
+                        let i = x + y * width;
+                        let mut acc_color = Vector::zero();
+                        let mut acc_luminance_squared = 0.0;
+                        let pass_samples = remaining_samples[pixel_samples.len()].min(iterations as u64) as usize;
+
+                        let first_sum = running_sums.len();
+                        for j in 0..pass_samples {
+                            let mut sampler = Sampler::new(seed, i as u64, (pass * iterations + j) as u64);
+                            let (start_position, dir) = camera.primary_ray(
+                                x, y, width, height,
+                                (sampler.gen_range(0.0, 1.0), sampler.gen_range(0.0, 1.0)),
+                                (sampler.gen_range(0.0, 1.0), sampler.gen_range(0.0, 1.0)));
+
+                            let color = scene.trace(
+                                start_position,
+                                dir,
+                                sun_dir,
+                                &mut sampler
+                            );
+                            acc_color = acc_color + color;
+                            acc_luminance_squared = acc_luminance_squared + color.luminance() * color.luminance();
+                            running_sums.push((acc_color, acc_luminance_squared));
+                            match scene.field.ray_cast(&Ray::new(start_position, dir)) {
+                                Some(_) => continue,
+                                None => break
+                            }
+                        }
+
+                        pixel_samples.push((first_sum, running_sums.len() - first_sum));
+                    }
                 }
 
-                acc_color = acc_color / color_count as f64;
+                let mut processed_samples = 0;
+                let finished;
+                {
+                    let mut accumulation = accumulation_mutex.lock().unwrap();
+                    accumulation.pending[tile.index].push(PassResult {
+                        pass: pass,
+                        pixel_samples: pixel_samples,
+                        running_sums: running_sums
+                    });
+
+                    // Publishes this pass if its turn has come, along with any later ones that were waiting on it
+                    loop {
+                        let next_pass = accumulation.tile_passes[tile.index];
+                        let position = match accumulation.pending[tile.index].iter().position(|result| result.pass == next_pass) {
+                            Some(position) => position,
+                            None => break
+                        };
+                        let result = accumulation.pending[tile.index].swap_remove(position);
+                        processed_samples = processed_samples + accumulation.publish(&tile, width, &result, samples_per_pixel, error_threshold);
+                    }
+
+                    {
+                        let mut colors = colors_mutex.lock().unwrap();
+                        for y in tile.y..tile.y + tile.height {
+                            for x in tile.x..tile.x + tile.width {
+                                let i = x + y * width;
+                                let stats = accumulation.pixels[i];
+                                if stats.count > 0 {
+                                    colors[i] = stats.acc_color / stats.count as f64;
+                                }
+                            }
+                        }
+                    }
+                    finished = accumulation.done_pixels == pixel_count;
+                }
 
-                let mut buffer = colors_mutex.lock().unwrap();
-                buffer[i] = acc_color;
+                status.add_samples(processed_samples as usize);
+                if finished {
+                    status.finish();
+                }
             }
         });
     }
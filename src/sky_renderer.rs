@@ -1,22 +1,33 @@
 use std::sync::{Arc, Mutex, Barrier};
 use std::thread;
+use std::time::{Duration, Instant};
 use std::f64::*;
 
 use vector::*;
 use atmosphere::*;
+use options::*;
+use render_status::*;
 
-const THREAD_COUNT: usize = 4;
-
-pub fn sky_renderer(color_mutex: Arc<Mutex<Vec<Vector>>>, width: usize, height: usize, threads: usize) {
-    let barrier = Arc::new(Barrier::new(THREAD_COUNT));
+pub fn sky_renderer(color_mutex: Arc<Mutex<Vec<Vector>>>, status: Arc<RenderStatus>, options: &Options) {
+    let width = options.width;
+    let height = options.height;
+    let threads = options.threads;
+    let fixed_sun_dir = options.sun_direction;
+    // Every pass is one sample per pixel, and one frame of the animation when the sun isn't fixed
+    let passes = if fixed_sun_dir.is_some() { 1 } else { options.samples_per_pixel };
+    let barrier = Arc::new(Barrier::new(threads));
+    let start = Instant::now();
+    let time_limit = Duration::from_secs(options.time_limit);
     {
         for t in 0..threads {
             let color_mutex = color_mutex.clone();
             let barrier = barrier.clone();
+            let status = status.clone();
             thread::spawn(move || {
                 let mut sun_theta: f64 = 0.0;
+                let mut pass = 0;
                 loop {
-                    let sun_dir = Vector::new(sun_theta.cos(), sun_theta.sin(), 0.0);
+                    let sun_dir = fixed_sun_dir.unwrap_or(Vector::new(sun_theta.cos(), sun_theta.sin(), 0.0));
                     for j in 0..height {
                         let y = 2.0 * (j as f64 + 0.5) / (height as f64 - 1.0) - 1.0;
                         for i in 0..width {
@@ -31,11 +42,21 @@ pub fn sky_renderer(color_mutex: Arc<Mutex<Vec<Vector>>>, width: usize, height:
                                     let mut colors = color_mutex.lock().unwrap();
                                     colors[i + width * j] = color;
                                 }
+                                status.add_samples(1);
                             }
                         }
                     }
 
+                    // A fixed sun only needs a single pass, otherwise the animation runs for the requested
+                    // samples or until the time limit
+                    pass = pass + 1;
+                    if barrier.wait().is_leader() && (pass >= passes || start.elapsed() >= time_limit) {
+                        status.finish();
+                    }
                     barrier.wait();
+                    if status.is_finished() {
+                        break;
+                    }
                     sun_theta = sun_theta + consts::PI / 200.0;
                 }
             });
@@ -0,0 +1,201 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use aabb::*;
+use std::f64::*;
+
+// How two distances are merged within the blend radius
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Blend {
+    // Quadratic smin, only affects points closer than the radius to both surfaces
+    Polynomial(f64),
+    // Log-sum-exp smin, softer and affects the whole field
+    Exponential(f64)
+}
+
+impl Blend {
+    // Returns the blended distance and how much of the second field shows through
+    pub fn smooth_min(&self, dist1: f64, dist2: f64) -> (f64, f64) {
+        match *self {
+            Blend::Polynomial(radius) => {
+                if !(radius > 0.0) {
+                    return if dist1 < dist2 { (dist1, 0.0) } else { (dist2, 1.0) };
+                }
+                let h = (0.5 + 0.5 * (dist2 - dist1) / radius).max(0.0).min(1.0);
+                (dist2 * (1.0 - h) + dist1 * h - radius * h * (1.0 - h), 1.0 - h)
+            },
+            Blend::Exponential(radius) => {
+                if !(radius > 0.0) {
+                    return if dist1 < dist2 { (dist1, 0.0) } else { (dist2, 1.0) };
+                }
+                // Shifted by the minimum so the exponentials can't overflow
+                let closest = dist1.min(dist2);
+                let dist = closest - radius * (1.0 + (-(dist1 - dist2).abs() / radius).exp()).ln();
+                (dist, 1.0 / (1.0 + ((dist2 - dist1) / radius).exp()))
+            }
+        }
+    }
+
+    // Furthest the blended surface can bulge out past either field
+    pub fn reach(&self) -> f64 {
+        match *self {
+            Blend::Polynomial(radius) => radius.max(0.0) * 0.25,
+            Blend::Exponential(radius) => radius.max(0.0) * consts::LN_2
+        }
+    }
+
+    pub fn smooth_max(&self, dist1: f64, dist2: f64) -> (f64, f64) {
+        let (dist, amount) = self.smooth_min(-dist1, -dist2);
+        (-dist, amount)
+    }
+}
+
+// The gradient of both smooth minimums is the blend of the gradients, so the normals mix the same way
+fn blended_normal(normal1: Vector, normal2: Vector, amount: f64) -> Option<Vector> {
+    let normal = Vector::interpolate(normal1, normal2, amount);
+    if normal.length_squared() > 0.0 {
+        Some(normal.normalize())
+    } else {
+        None
+    }
+}
+
+pub struct SmoothUnion<T1: Field, T2: Field> {
+    pub field1: T1,
+    pub field2: T2,
+    pub blend: Blend,
+    bounds: Aabb
+}
+
+impl<T1: Field, T2: Field> SmoothUnion<T1, T2> {
+    pub fn new(scene1: Scene<T1>, scene2: Scene<T2>, blend: Blend) -> Scene<SmoothUnion<T1, T2>> {
+        let bounds = scene1.field.bounds().union(scene2.field.bounds()).expand(blend.reach());
+        Scene {
+            field: SmoothUnion {
+                field1: scene1.field,
+                field2: scene2.field,
+                blend: blend,
+                bounds: bounds
+            }
+        }
+    }
+
+    fn blend_amount(&self, pos: Vector) -> f64 {
+        self.blend.smooth_min(self.field1.distance(pos), self.field2.distance(pos)).1
+    }
+}
+
+impl<T1: Field, T2: Field> Field for SmoothUnion<T1, T2> {
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.blend.smooth_min(self.field1.distance(pos), self.field2.distance(pos)).0
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let amount = self.blend_amount(pos);
+        blended_normal(self.field1.normal(pos, epsilon), self.field2.normal(pos, epsilon), amount)
+            .unwrap_or_else(|| numerical_normal(self, pos, epsilon))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        let amount = self.blend_amount(pos);
+        Characteristics::interpolate(self.field1.characteristics(pos), self.field2.characteristics(pos), amount)
+    }
+}
+
+pub struct SmoothIntersection<T1: Field, T2: Field> {
+    pub field1: T1,
+    pub field2: T2,
+    pub blend: Blend,
+    bounds: Aabb
+}
+
+impl<T1: Field, T2: Field> SmoothIntersection<T1, T2> {
+    pub fn new(scene1: Scene<T1>, scene2: Scene<T2>, blend: Blend) -> Scene<SmoothIntersection<T1, T2>> {
+        let bounds = scene1.field.bounds().intersection(scene2.field.bounds());
+        Scene {
+            field: SmoothIntersection {
+                field1: scene1.field,
+                field2: scene2.field,
+                blend: blend,
+                bounds: bounds
+            }
+        }
+    }
+
+    fn blend_amount(&self, pos: Vector) -> f64 {
+        self.blend.smooth_max(self.field1.distance(pos), self.field2.distance(pos)).1
+    }
+}
+
+impl<T1: Field, T2: Field> Field for SmoothIntersection<T1, T2> {
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.blend.smooth_max(self.field1.distance(pos), self.field2.distance(pos)).0
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let amount = self.blend_amount(pos);
+        blended_normal(self.field1.normal(pos, epsilon), self.field2.normal(pos, epsilon), amount)
+            .unwrap_or_else(|| numerical_normal(self, pos, epsilon))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        let amount = self.blend_amount(pos);
+        Characteristics::interpolate(self.field1.characteristics(pos), self.field2.characteristics(pos), amount)
+    }
+}
+
+// Carves the second field out of the first, the carved surface takes on the second field's characteristics
+pub struct SmoothDifference<T1: Field, T2: Field> {
+    pub field1: T1,
+    pub field2: T2,
+    pub blend: Blend,
+    bounds: Aabb
+}
+
+impl<T1: Field, T2: Field> SmoothDifference<T1, T2> {
+    pub fn new(scene1: Scene<T1>, scene2: Scene<T2>, blend: Blend) -> Scene<SmoothDifference<T1, T2>> {
+        let bounds = scene1.field.bounds();
+        Scene {
+            field: SmoothDifference {
+                field1: scene1.field,
+                field2: scene2.field,
+                blend: blend,
+                bounds: bounds
+            }
+        }
+    }
+
+    fn blend_amount(&self, pos: Vector) -> f64 {
+        self.blend.smooth_max(self.field1.distance(pos), -self.field2.distance(pos)).1
+    }
+}
+
+impl<T1: Field, T2: Field> Field for SmoothDifference<T1, T2> {
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.blend.smooth_max(self.field1.distance(pos), -self.field2.distance(pos)).0
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        let amount = self.blend_amount(pos);
+        blended_normal(self.field1.normal(pos, epsilon), -self.field2.normal(pos, epsilon), amount)
+            .unwrap_or_else(|| numerical_normal(self, pos, epsilon))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        let amount = self.blend_amount(pos);
+        Characteristics::interpolate(self.field1.characteristics(pos), self.field2.characteristics(pos), amount)
+    }
+}
@@ -0,0 +1,107 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TileOrder {
+    Scanline,
+    Spiral,
+    Hilbert
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Tile {
+    pub index: usize,
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+// Hands out tiles to render threads, walking every tile once per pass
+pub struct TileQueue {
+    pub tiles: Vec<Tile>,
+    next: AtomicUsize
+}
+
+impl TileQueue {
+    pub fn new(width: usize, height: usize, tile_size: usize, order: TileOrder) -> TileQueue {
+        let columns = (width + tile_size - 1) / tile_size;
+        let rows = (height + tile_size - 1) / tile_size;
+
+        let mut grid = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                grid.push((column, row));
+            }
+        }
+
+        match order {
+            TileOrder::Scanline => {},
+            TileOrder::Spiral => {
+                // Rings around the center tile, walked by angle within each ring
+                let center_x = (columns as f64 - 1.0) / 2.0;
+                let center_y = (rows as f64 - 1.0) / 2.0;
+                let key = |&(column, row): &(usize, usize)| {
+                    let dx = column as f64 - center_x;
+                    let dy = row as f64 - center_y;
+                    (dx.abs().max(dy.abs()), dy.atan2(dx))
+                };
+                grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(CmpOrdering::Equal));
+            },
+            TileOrder::Hilbert => {
+                let mut size = 1;
+                while size < columns.max(rows) {
+                    size = size * 2;
+                }
+                grid.sort_by_key(|&(column, row)| hilbert_index(size, column, row));
+            }
+        }
+
+        let tiles = grid.iter().enumerate().map(|(index, &(column, row))| {
+            let x = column * tile_size;
+            let y = row * tile_size;
+            Tile {
+                index: index,
+                x: x,
+                y: y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y)
+            }
+        }).collect();
+
+        TileQueue {
+            tiles: tiles,
+            next: AtomicUsize::new(0)
+        }
+    }
+
+    // Returns the pass and tile of the next piece of work
+    pub fn claim(&self) -> (usize, Tile) {
+        let work_item = self.next.fetch_add(1, Ordering::SeqCst);
+        (work_item / self.tiles.len(), self.tiles[work_item % self.tiles.len()])
+    }
+}
+
+// Distance along a Hilbert curve covering a size by size grid, size must be a power of two
+fn hilbert_index(size: usize, x: usize, y: usize) -> usize {
+    let mut x = x;
+    let mut y = y;
+    let mut index = 0;
+    let mut s = size / 2;
+    while s > 0 {
+        let rx = if x & s > 0 { 1 } else { 0 };
+        let ry = if y & s > 0 { 1 } else { 0 };
+        index = index + s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            let temp = x;
+            x = y;
+            y = temp;
+        }
+        s = s / 2;
+    }
+    index
+}
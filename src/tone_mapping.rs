@@ -0,0 +1,104 @@
+use vector::*;
+
+// 4x4 ordered dither thresholds, spread over a single 8 bit step
+const BAYER_MATRIX: [[f64; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0]
+];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ToneMapOperator {
+    Linear,
+    Reinhard,
+    ExtendedReinhard(f64),
+    AcesFilmic,
+    Uncharted2
+}
+
+impl ToneMapOperator {
+    pub fn apply(self, color: Vector) -> Vector {
+        match self {
+            ToneMapOperator::Linear => map_channels(color, |c| c),
+            ToneMapOperator::Reinhard => map_channels(color, |c| c / (1.0 + c)),
+            ToneMapOperator::ExtendedReinhard(white_point) => {
+                let white_squared = white_point * white_point;
+                map_channels(color, |c| c * (1.0 + c / white_squared) / (1.0 + c))
+            },
+            ToneMapOperator::AcesFilmic => map_channels(color, |c| {
+                (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)
+            }),
+            ToneMapOperator::Uncharted2 => {
+                let white_scale = 1.0 / uncharted2_curve(11.2);
+                map_channels(color, |c| uncharted2_curve(c * 2.0) * white_scale)
+            }
+        }
+    }
+}
+
+pub struct ToneMapper {
+    pub operator: ToneMapOperator,
+    pub exposure: f64,
+    pub dither: bool
+}
+
+impl ToneMapper {
+    pub fn new(operator: ToneMapOperator) -> ToneMapper {
+        ToneMapper {
+            operator: operator,
+            exposure: 1.0,
+            dither: true
+        }
+    }
+
+    pub fn to_int_color(&self, color: Vector, x: usize, y: usize) -> u32 {
+        let mapped = self.operator.apply(color * self.exposure);
+        let offset = if self.dither {
+            (BAYER_MATRIX[y % 4][x % 4] + 0.5) / 16.0
+        } else {
+            0.5
+        };
+
+        let r = quantize(srgb_oetf(mapped.x), offset);
+        let g = quantize(srgb_oetf(mapped.y), offset);
+        let b = quantize(srgb_oetf(mapped.z), offset);
+
+        r << 16 | g << 8 | b
+    }
+}
+
+pub fn srgb_oetf(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn quantize(value: f64, offset: f64) -> u32 {
+    match value * 255.0 + offset {
+        v if v > 255.0 => 255,
+        v if v > 0.0 => v as u32,
+        _ => 0
+    }
+}
+
+fn map_channels<F>(color: Vector, f: F) -> Vector
+    where F: Fn(f64) -> f64 {
+    Vector {
+        x: f(color.x.max(0.0)),
+        y: f(color.y.max(0.0)),
+        z: f(color.z.max(0.0))
+    }
+}
+
+fn uncharted2_curve(x: f64) -> f64 {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
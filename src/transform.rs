@@ -0,0 +1,112 @@
+use vector::*;
+use scene::*;
+use distance_field::*;
+use characteristics::*;
+use interval::*;
+use ray::*;
+use aabb::*;
+
+// Places a field with an affine transform. Points and rays are carried into the field's own space by
+// the inverse, and the results are carried back
+pub struct Transformed<T: Field> {
+    pub field: T,
+    transform: Matrix,
+    inverse: Matrix,
+    // Distances in the field's space shrink by at most this much in world space
+    min_scale: f64,
+    bounds: Aabb
+}
+
+impl<T: Field> Transformed<T> {
+    // None when the transform can't be inverted, or shrinks space too far for distances to be carried back
+    pub fn new(scene: Scene<T>, transform: Matrix) -> Option<Scene<Transformed<T>>> {
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return None
+        };
+        let min_scale = transform.min_scale();
+        if !(min_scale > 0.0) {
+            return None;
+        }
+
+        // The box around the transformed corners of the field's box holds the transformed field
+        let local_bounds = scene.field.bounds();
+        let bounds = if local_bounds.is_empty() {
+            local_bounds
+        } else if local_bounds.is_finite() {
+            local_bounds.corners().iter().fold(Aabb::empty(), |bounds, &corner| bounds.grow(transform.transform_point(corner)))
+        } else {
+            Aabb::infinite()
+        };
+        Some(Scene {
+            field: Transformed {
+                field: scene.field,
+                transform: transform,
+                inverse: inverse,
+                min_scale: min_scale,
+                bounds: bounds
+            }
+        })
+    }
+
+    // Returns the ray in local space with a normalized direction, and the world distance per unit of
+    // local distance along it. A world step covers at most 1 / min_scale of local space, so the
+    // normal step is scaled up by that much
+    fn local_ray(&self, ray: &Ray) -> (Ray, f64) {
+        let local_dir = self.inverse.transform_vector(ray.direction);
+        let length = local_dir.length();
+        let local = Ray {
+            origin: self.inverse.transform_point(ray.origin),
+            direction: local_dir / length,
+            footprint: ray.footprint / self.min_scale,
+            spread: ray.spread / (length * self.min_scale)
+        };
+        (local, 1.0 / length)
+    }
+
+    fn world_normal(&self, local_normal: Vector) -> Vector {
+        self.inverse.transform_transposed(local_normal).normalize()
+    }
+
+    fn world_hit(&self, hit: Hit, world_per_local: f64) -> Hit {
+        Hit {
+            t: hit.t * world_per_local,
+            position: self.transform.transform_point(hit.position),
+            geometric_normal: self.world_normal(hit.geometric_normal),
+            shading_normal: self.world_normal(hit.shading_normal),
+            ..hit
+        }
+    }
+}
+
+impl<T: Field> Field for Transformed<T> {
+    fn ray_cast(&self, ray: &Ray) -> Option<Hit> {
+        let (local_ray, world_per_local) = self.local_ray(ray);
+        self.field.ray_cast(&local_ray).map(|hit| self.world_hit(hit, world_per_local))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let (local_ray, world_per_local) = self.local_ray(ray);
+        self.field.intervals(&local_ray).iter()
+            .map(|interval| Interval::new(interval.start * world_per_local, interval.end * world_per_local).with_hits(
+                interval.enter.map(|hit| self.world_hit(hit, world_per_local)),
+                interval.exit.map(|hit| self.world_hit(hit, world_per_local))))
+            .collect()
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn distance(&self, pos: Vector) -> f64 {
+        self.field.distance(self.inverse.transform_point(pos)) * self.min_scale
+    }
+
+    fn normal(&self, pos: Vector, epsilon: f64) -> Vector {
+        self.world_normal(self.field.normal(self.inverse.transform_point(pos), epsilon / self.min_scale))
+    }
+
+    fn characteristics(&self, pos: Vector) -> Characteristics {
+        self.field.characteristics(self.inverse.transform_point(pos))
+    }
+}
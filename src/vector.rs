@@ -41,23 +41,45 @@ impl Vector {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
-    pub fn to_int_color(self) -> u32 {
-        let r = match self.x * 255.0 {
-            r if r > 255.0 => 255.0,
-            r => r
-        };
+    pub fn abs(self) -> Vector {
+        Vector {
+            x: self.x.abs(),
+            y: self.y.abs(),
+            z: self.z.abs()
+        }
+    }
 
-        let g = match self.y * 255.0 {
-            g if g > 255.0 => 255.0,
-            g => g
-        };
+    pub fn max(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+            z: self.z.max(other.z)
+        }
+    }
 
-        let b = match self.z * 255.0 {
-            b if b > 255.0 => 255.0,
-            b => b
-        };
+    pub fn min(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            z: self.z.min(other.z)
+        }
+    }
+
+    // Component along the axis numbered 0 for x, 1 for y and 2 for z
+    pub fn component(self, axis: usize) -> f64 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z
+        }
+    }
+
+    pub fn max_component(self) -> f64 {
+        self.x.max(self.y).max(self.z)
+    }
 
-        (r as u32) << 16 | (g as u32) << 8 | (b as u32)
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
     }
 
     pub fn interpolate(a: Vector, b: Vector, amount: f64) -> Vector {
@@ -88,8 +110,7 @@ impl Vector {
         }
     }
 
-    pub fn random() -> Vector {
-        let mut rng = thread_rng();
+    pub fn random<R: Rng>(rng: &mut R) -> Vector {
         let theta = rng.gen_range(0.0, 2.0 * consts::PI);
         let z = rng.gen_range(-1.0, 1.0);
         Vector {
@@ -174,3 +195,279 @@ impl Div<f64> for Vector {
         }
     }
 }
+
+impl Div for Vector {
+    type Output = Vector;
+
+    fn div(self, rhs: Vector) -> Vector {
+        Vector {
+            x: self.x / rhs.x,
+            y: self.y / rhs.y,
+            z: self.z / rhs.z
+        }
+    }
+}
+
+// Affine transform stored as the top three rows of a 4x4 matrix, the bottom row is always 0 0 0 1
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Matrix {
+    pub rows: [[f64; 4]; 3]
+}
+
+impl Matrix {
+    pub fn identity() -> Matrix {
+        Matrix {
+            rows: [[1.0, 0.0, 0.0, 0.0],
+                   [0.0, 1.0, 0.0, 0.0],
+                   [0.0, 0.0, 1.0, 0.0]]
+        }
+    }
+
+    pub fn translation(offset: Vector) -> Matrix {
+        Matrix {
+            rows: [[1.0, 0.0, 0.0, offset.x],
+                   [0.0, 1.0, 0.0, offset.y],
+                   [0.0, 0.0, 1.0, offset.z]]
+        }
+    }
+
+    pub fn scale(factors: Vector) -> Matrix {
+        Matrix {
+            rows: [[factors.x, 0.0, 0.0, 0.0],
+                   [0.0, factors.y, 0.0, 0.0],
+                   [0.0, 0.0, factors.z, 0.0]]
+        }
+    }
+
+    pub fn rotation(rotation: Quaternion) -> Matrix {
+        let q = rotation.normalize();
+        let (w, x, y, z) = (q.w, q.x, q.y, q.z);
+        Matrix {
+            rows: [[1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+                   [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+                   [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0]]
+        }
+    }
+
+    pub fn to_4x4(&self) -> [[f64; 4]; 4] {
+        [self.rows[0], self.rows[1], self.rows[2], [0.0, 0.0, 0.0, 1.0]]
+    }
+
+    pub fn transform_point(&self, point: Vector) -> Vector {
+        self.transform_vector(point) + Vector::new(self.rows[0][3], self.rows[1][3], self.rows[2][3])
+    }
+
+    // Applies only the linear part, for directions and offsets
+    pub fn transform_vector(&self, vector: Vector) -> Vector {
+        let r = &self.rows;
+        Vector::new(
+            r[0][0] * vector.x + r[0][1] * vector.y + r[0][2] * vector.z,
+            r[1][0] * vector.x + r[1][1] * vector.y + r[1][2] * vector.z,
+            r[2][0] * vector.x + r[2][1] * vector.y + r[2][2] * vector.z)
+    }
+
+    // Applies the transpose of the linear part, the inverse's transpose is what carries normals
+    pub fn transform_transposed(&self, vector: Vector) -> Vector {
+        let r = &self.rows;
+        Vector::new(
+            r[0][0] * vector.x + r[1][0] * vector.y + r[2][0] * vector.z,
+            r[0][1] * vector.x + r[1][1] * vector.y + r[2][1] * vector.z,
+            r[0][2] * vector.x + r[1][2] * vector.y + r[2][2] * vector.z)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let r = &self.rows;
+        r[0][0] * (r[1][1] * r[2][2] - r[1][2] * r[2][1])
+            - r[0][1] * (r[1][0] * r[2][2] - r[1][2] * r[2][0])
+            + r[0][2] * (r[1][0] * r[2][1] - r[1][1] * r[2][0])
+    }
+
+    // None when the transform squashes space flat, or too close to flat to invert in doubles
+    pub fn inverse(&self) -> Option<Matrix> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let r = &self.rows;
+        let mut inverse = Matrix {
+            rows: [[(r[1][1] * r[2][2] - r[1][2] * r[2][1]) / det,
+                    (r[0][2] * r[2][1] - r[0][1] * r[2][2]) / det,
+                    (r[0][1] * r[1][2] - r[0][2] * r[1][1]) / det,
+                    0.0],
+                   [(r[1][2] * r[2][0] - r[1][0] * r[2][2]) / det,
+                    (r[0][0] * r[2][2] - r[0][2] * r[2][0]) / det,
+                    (r[0][2] * r[1][0] - r[0][0] * r[1][2]) / det,
+                    0.0],
+                   [(r[1][0] * r[2][1] - r[1][1] * r[2][0]) / det,
+                    (r[0][1] * r[2][0] - r[0][0] * r[2][1]) / det,
+                    (r[0][0] * r[1][1] - r[0][1] * r[1][0]) / det,
+                    0.0]]
+        };
+        let translation = -inverse.transform_vector(Vector::new(r[0][3], r[1][3], r[2][3]));
+        inverse.rows[0][3] = translation.x;
+        inverse.rows[1][3] = translation.y;
+        inverse.rows[2][3] = translation.z;
+        // A nearly flat transform can have a representable determinant but an inverse that overflows
+        if inverse.rows.iter().any(|row| row.iter().any(|entry| !entry.is_finite())) {
+            return None;
+        }
+        Some(inverse)
+    }
+
+    // Smallest factor by which the transform can shrink a length, the smallest singular value of the
+    // linear part. Found from the eigenvalues of the symmetric matrix A^T A
+    pub fn min_scale(&self) -> f64 {
+        let columns = [
+            self.transform_vector(Vector::new(1.0, 0.0, 0.0)),
+            self.transform_vector(Vector::new(0.0, 1.0, 0.0)),
+            self.transform_vector(Vector::new(0.0, 0.0, 1.0))];
+        let mut m = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                m[i][j] = columns[i].dot(columns[j]);
+            }
+        }
+
+        let off_diagonal = m[0][1] * m[0][1] + m[0][2] * m[0][2] + m[1][2] * m[1][2];
+        if off_diagonal == 0.0 {
+            return m[0][0].min(m[1][1]).min(m[2][2]).max(0.0).sqrt();
+        }
+        let q = (m[0][0] + m[1][1] + m[2][2]) / 3.0;
+        let p2 = (m[0][0] - q).powi(2) + (m[1][1] - q).powi(2) + (m[2][2] - q).powi(2) + 2.0 * off_diagonal;
+        let p = (p2 / 6.0).sqrt();
+        let mut b = [[0.0; 3]; 3];
+        for i in 0..3 {
+            for j in 0..3 {
+                b[i][j] = (m[i][j] - if i == j { q } else { 0.0 }) / p;
+            }
+        }
+        let det_b = b[0][0] * (b[1][1] * b[2][2] - b[1][2] * b[2][1])
+            - b[0][1] * (b[1][0] * b[2][2] - b[1][2] * b[2][0])
+            + b[0][2] * (b[1][0] * b[2][1] - b[1][1] * b[2][0]);
+        let phi = (det_b / 2.0).max(-1.0).min(1.0).acos() / 3.0;
+        let smallest = q + 2.0 * p * (phi + 2.0 * consts::PI / 3.0).cos();
+        smallest.max(0.0).sqrt()
+    }
+}
+
+// Composes transforms so that (a * b) applies b first
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: Matrix) -> Matrix {
+        let a = self.to_4x4();
+        let b = rhs.to_4x4();
+        let mut rows = [[0.0; 4]; 3];
+        for i in 0..3 {
+            for j in 0..4 {
+                rows[i][j] = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j] + a[i][3] * b[3][j];
+            }
+        }
+        Matrix {
+            rows: rows
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+impl Quaternion {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quaternion {
+        Quaternion {
+            w: w,
+            x: x,
+            y: y,
+            z: z
+        }
+    }
+
+    // Rotation by angle radians around the axis, counterclockwise when the axis points at the viewer
+    pub fn from_axis_angle(axis: Vector, angle: f64) -> Quaternion {
+        let axis = axis.normalize();
+        let s = (angle / 2.0).sin();
+        Quaternion::new((angle / 2.0).cos(), axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    pub fn length(self) -> f64 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let length = self.length();
+        Quaternion::new(self.w / length, self.x / length, self.y / length, self.z / length)
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!((a - b).length() < 1.0e-9, "{:?} is not {:?}", a, b);
+    }
+
+    fn rotation() -> Matrix {
+        Matrix::rotation(Quaternion::from_axis_angle(Vector::new(1.0, 2.0, 3.0), 0.7))
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Matrix::translation(Vector::new(1.0, -2.0, 3.0)) * rotation() * Matrix::scale(Vector::new(2.0, 3.0, 0.5));
+        let inverse = transform.inverse().unwrap();
+        for &point in [Vector::zero(), Vector::new(1.0, 2.0, 3.0), Vector::new(-4.0, 0.5, 7.0)].iter() {
+            assert_close(inverse.transform_point(transform.transform_point(point)), point);
+            assert_close(transform.transform_point(inverse.transform_point(point)), point);
+        }
+    }
+
+    #[test]
+    fn flat_transforms_have_no_inverse() {
+        assert!(Matrix::scale(Vector::new(1.0, 0.0, 1.0)).inverse().is_none());
+        // Representable determinant, but the inverse overflows
+        assert!(Matrix::scale(Vector::new(1.0e-310, 1.0, 1.0)).inverse().is_none());
+        // Determinant underflows to zero
+        assert!(Matrix::scale(Vector::one() * 1.0e-110).inverse().is_none());
+    }
+
+    #[test]
+    fn min_scale_is_the_smallest_stretch() {
+        assert_eq!(Matrix::identity().min_scale(), 1.0);
+        assert_eq!(Matrix::scale(Vector::new(2.0, 3.0, 0.5)).min_scale(), 0.5);
+        assert!((rotation().min_scale() - 1.0).abs() < 1.0e-9);
+        assert!(((rotation() * Matrix::scale(Vector::new(2.0, 0.25, 3.0))).min_scale() - 0.25).abs() < 1.0e-9);
+
+        // A shear, whose smallest singular value is (sqrt(5) - 1) / 2
+        let shear = Matrix {
+            rows: [[1.0, 1.0, 0.0, 0.0],
+                   [0.0, 1.0, 0.0, 0.0],
+                   [0.0, 0.0, 1.0, 0.0]]
+        };
+        let expected = (5.0f64.sqrt() - 1.0) / 2.0;
+        assert!((shear.min_scale() - expected).abs() < 1.0e-9);
+        // No direction is shrunk more than that
+        for i in 0..1000 {
+            let angle = i as f64 * 0.01;
+            let direction = Vector::new(angle.cos(), angle.sin(), (angle * 3.0).sin()).normalize();
+            assert!(shear.transform_vector(direction).length() >= expected - 1.0e-9);
+        }
+    }
+}